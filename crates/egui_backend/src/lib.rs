@@ -30,10 +30,28 @@ use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
 /// Intended to provide a common struct which all window backends accept as their configuration.
 /// In future, might add more options like initial window size/title etc..
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct BackendConfig {
     /// The kind of graphics api that we plan to use the window with
     pub gfx_api_type: GfxApiType,
+    /// how many points (logical pixels) to scroll for one line (one notch of a regular mouse wheel).
+    /// all window backends convert their scroll deltas into points using this, so that scrolling feels the same with any backend.
+    /// precise (pixel) scroll deltas from trackpads are already in points and are not affected.
+    /// default is `DEFAULT_SCROLL_POINTS_PER_LINE`. increase it to scroll faster.
+    pub scroll_points_per_line: f32,
+}
+/// window backends set `RawInput::predicted_dt` to the duration of the last frame, clamped to this value (in seconds).
+/// so that animations don't jump after a stall (eg: window being dragged or a breakpoint)
+pub const MAX_PREDICTED_DT: f32 = 0.1;
+/// 50 points per line, same as egui's official winit integration.
+pub const DEFAULT_SCROLL_POINTS_PER_LINE: f32 = 50.0;
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            gfx_api_type: Default::default(),
+            scroll_points_per_line: DEFAULT_SCROLL_POINTS_PER_LINE,
+        }
+    }
 }
 /// Gfx Apis like Opengl (Gl-es) require some special config while creating a window.
 /// OTOH, modern APIs like metal/vk/dx deal with configuration themselves after creating a window.
@@ -58,6 +76,46 @@ impl Default for GfxApiType {
     }
 }
 
+/// How the cursor should be restricted to the window. used with `WindowBackend::set_cursor_grab`.
+/// some window backends don't support all modes, and will fallback to the closest supported mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorGrabMode {
+    /// cursor can move freely
+    #[default]
+    None,
+    /// cursor can move, but it cannot leave the window area
+    Confined,
+    /// cursor is locked in place. useful for first person camera controls.
+    /// the absolute cursor position is meaningless in this mode, so use relative mouse motion instead.
+    Locked,
+}
+
+/// lets apps sandbox or virtualize the clipboard (eg: secure apps which must not touch the system clipboard).
+/// set it as `clipboard_handler` in the window backend's configuration, and egui's copy / cut / paste go through it
+/// instead of the system clipboard.
+pub trait ClipboardHandler {
+    /// text to paste. `None` if there's nothing to paste (or pasting is not allowed).
+    fn get(&mut self) -> Option<String>;
+    /// called with the text that was copied or cut in egui
+    fn set(&mut self, text: String);
+}
+
+/// error type returned by `WindowBackend::run_event_loop` when the user app asks to exit with an error.
+pub type AppError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The user app returns this from `UserAppData::control_flow` after every frame, to tell the window backend
+/// whether it wants to keep running the event loop.
+#[derive(Debug, Default)]
+pub enum AppControlFlow {
+    /// keep running the event loop
+    #[default]
+    Continue,
+    /// break out of the event loop cleanly
+    Exit,
+    /// break out of the event loop and return this error from `WindowBackend::run_event_loop` (where possible)
+    Error(AppError),
+}
+
 /// This is the output from egui that renderer needs.
 /// meshes and textures_delta come from egui directly.
 /// window backend needs to also provide screensize in logical coords, scale and physical framebuffer
@@ -70,6 +128,119 @@ pub struct EguiGfxData {
     /// this is what you provided to `RawInput` for `Context::begin_frame()`
     /// * used for screen_size uniform in shaders
     pub screen_size_logical: [f32; 2],
+    /// physical rect `[x, y, width, height]` of the framebuffer into which egui should be drawn.
+    /// `None` means the whole framebuffer. window backends set this when a virtual screen size is set
+    /// with `WindowBackend::set_virtual_screen_size` (see `Letterbox`).
+    /// gfx backends which don't support it just draw into the whole framebuffer.
+    pub viewport: Option<[f32; 4]>,
+}
+
+/// fits a fixed virtual (logical) screen size into the framebuffer while keeping its aspect ratio.
+/// the remaining area on the sides (or top and bottom) is left as letterbox bars.
+/// used by window backends when `WindowBackend::set_virtual_screen_size` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    /// the logical size that egui lays out the ui in
+    pub virtual_size: [f32; 2],
+    /// physical rect `[x, y, width, height]` of the framebuffer into which the virtual screen is drawn (centered).
+    pub viewport: [f32; 4],
+    /// physical pixels per virtual point. use this as `RawInput::pixels_per_point`
+    pub pixels_per_point: f32,
+}
+impl Letterbox {
+    pub fn new(virtual_size: [f32; 2], framebuffer_size: [u32; 2]) -> Self {
+        let framebuffer_size = [framebuffer_size[0] as f32, framebuffer_size[1] as f32];
+        // avoid a zero scale for minimized windows. otherwise, we would divide by zero when transforming positions
+        let pixels_per_point = (framebuffer_size[0] / virtual_size[0])
+            .min(framebuffer_size[1] / virtual_size[1])
+            .max(f32::EPSILON);
+        let width = virtual_size[0] * pixels_per_point;
+        let height = virtual_size[1] * pixels_per_point;
+        Self {
+            virtual_size,
+            viewport: [
+                (framebuffer_size[0] - width) / 2.0,
+                (framebuffer_size[1] - height) / 2.0,
+                width,
+                height,
+            ],
+            pixels_per_point,
+        }
+    }
+    /// transforms a position in physical pixels of the window into virtual screen space.
+    /// positions inside the letterbox bars end up outside of `[0, 0]..virtual_size`.
+    pub fn physical_to_virtual(&self, position: [f32; 2]) -> egui::Pos2 {
+        egui::pos2(
+            (position[0] - self.viewport[0]) / self.pixels_per_point,
+            (position[1] - self.viewport[1]) / self.pixels_per_point,
+        )
+    }
+    /// inverse of `Self::physical_to_virtual`. transforms a position in virtual screen space into physical pixels of the window.
+    /// useful for picking or reading back pixels (eg: `WgpuBackend::read_rect`) under an egui widget.
+    pub fn virtual_to_physical(&self, position: egui::Pos2) -> [f32; 2] {
+        [
+            self.viewport[0] + position.x * self.pixels_per_point,
+            self.viewport[1] + position.y * self.pixels_per_point,
+        ]
+    }
+    /// transforms a rect in virtual screen space into a physical rect `[x, y, width, height]` of the window.
+    pub fn virtual_rect_to_physical(&self, rect: egui::Rect) -> [f32; 4] {
+        let [x, y] = self.virtual_to_physical(rect.min);
+        [
+            x,
+            y,
+            rect.width() * self.pixels_per_point,
+            rect.height() * self.pixels_per_point,
+        ]
+    }
+}
+
+/// pushes `event` into `events`, but if both `event` and the last event are `Event::PointerMoved`, it replaces the last event
+/// instead. so, consecutive moves are coalesced into the latest position, while moves before / after other events
+/// (eg: `Event::PointerButton`) are kept. used by window backends with a `coalesce_pointer_moves` option.
+pub fn push_coalesced_event(events: &mut Vec<egui::Event>, event: egui::Event) {
+    if let (egui::Event::PointerMoved(_), Some(last @ egui::Event::PointerMoved(_))) =
+        (&event, events.last_mut())
+    {
+        *last = event;
+    } else {
+        events.push(event);
+    }
+}
+
+/// remembers the output of the previous frame to detect frames which would look exactly the same.
+/// used by window backends with a `render_only_if_dirty` option, which call `GfxBackend::discard_frame` for such frames
+/// instead of `GfxBackend::render` and `GfxBackend::present` (unless the gfx backend can't discard them). useful for low power overlays.
+#[derive(Debug, Default)]
+pub struct FrameDirtyTracker {
+    last_shapes: Vec<egui::epaint::ClippedShape>,
+    last_screen_size_logical: [f32; 2],
+    last_pixels_per_point: f32,
+}
+impl FrameDirtyTracker {
+    /// returns true if the frame must be drawn. i.e. if the framebuffer was resized, there are texture updates,
+    /// egui wants to repaint immediately (animations) or the shapes / screen size / scale changed since the last dirty frame.
+    /// paint callbacks are compared by pointer, so frames with callbacks are usually dirty.
+    pub fn is_dirty(
+        &mut self,
+        output: &egui::FullOutput,
+        screen_size_logical: [f32; 2],
+        pixels_per_point: f32,
+        resized: bool,
+    ) -> bool {
+        let dirty = resized
+            || !output.textures_delta.is_empty()
+            || output.repaint_after.is_zero()
+            || self.last_screen_size_logical != screen_size_logical
+            || self.last_pixels_per_point != pixels_per_point
+            || self.last_shapes != output.shapes;
+        if dirty {
+            self.last_shapes = output.shapes.clone();
+            self.last_screen_size_logical = screen_size_logical;
+            self.last_pixels_per_point = pixels_per_point;
+        }
+        dirty
+    }
 }
 
 /// Implement this trait for your windowing backend. the main responsibility of a
@@ -78,6 +249,10 @@ pub struct EguiGfxData {
 /// 2. convert events to egui raw input and give it to egui context's begin_frame
 /// 3. provide framebuffer resize (optional) details to Gfx Backend when preparing the frame (surface / swapchain etc..)
 /// 4. run event loop and call the necessary functions of Gfx and UserApp
+///
+/// the windowing feature methods (cursor, raw input access, scale factor, window attributes like decorations or size limits)
+/// are optional, so a backend only needs to override the ones it supports. by default, setters log a warning and do nothing,
+/// while getters return a neutral value (no mouse motion, a scale factor of 1.0 or `None`).
 pub trait WindowBackend: Sized {
     /// This will be WindowBackend's configuration. if necessary, just add Boxed closures as its
     /// fields and run them before window creation, after window creation etc.. to provide maximum
@@ -98,14 +273,28 @@ pub trait WindowBackend: Sized {
     /// image with an outdated size. you will need to provide the *latest* size for succesful creation of surface frame.
     /// if the return value is `None`, the window doesn't exist yet. eg: on android, after suspend but before resume event.
     fn get_live_physical_size_framebuffer(&mut self) -> Option<[u32; 2]>;
+    /// raw window handle of `Self::get_window`, so that generic code over `W: WindowBackend` can create surfaces
+    /// or interop with other libraries. `None` if the window doesn't exist (eg: on android, while suspended).
+    fn raw_window_handle(&mut self) -> Option<raw_window_handle::RawWindowHandle> {
+        self.get_window().map(|window| window.raw_window_handle())
+    }
+    /// raw display handle of `Self::get_window`. `None` if the window doesn't exist, just like `Self::raw_window_handle`
+    fn raw_display_handle(&mut self) -> Option<raw_window_handle::RawDisplayHandle> {
+        self.get_window().map(|window| window.raw_display_handle())
+    }
 
     /// Run the event loop. different backends run it differently, so they all need to take care and
     /// call the Gfx or UserApp functions at the right time.
+    ///
+    /// The loop stops when the window is closed or when `UserAppData::control_flow` asks to exit.
+    /// If the app exits with `AppControlFlow::Error`, that error is returned after the user app and the gfx backend are dropped.
+    /// some platforms don't let the event loop return (winit on wasm, android and ios). there, the error is logged and
+    /// the process exits with an error code instead, so no cleanup runs.
     fn run_event_loop<G: GfxBackend<Self> + 'static, U: UserAppData<Self, G> + 'static>(
         self,
         gfx_backend: G,
         user_app: U,
-    );
+    ) -> Result<(), AppError>;
     /// config if GfxBackend needs them. usually tells the GfxBackend whether we have an opengl or non-opengl window.
     /// for example, if a vulkan backend gets a window with opengl, it can gracefully panic instead of probably segfaulting.
     /// this also serves as an indicator for opengl gfx backends, on whether this backend supports `swap_buffers` or `get_proc_address` functions.
@@ -116,6 +305,122 @@ pub trait WindowBackend: Sized {
     fn swap_buffers(&mut self) {
         unimplemented!("swap buffers is not implemented for this window backend");
     }
+    /// grab (confine or lock) the cursor to the window.
+    fn set_cursor_grab(&mut self, mode: CursorGrabMode) {
+        tracing::warn!(
+            "set_cursor_grab is not supported by this window backend. ignoring {mode:?}"
+        );
+    }
+    /// show or hide the cursor while it is over the window.
+    fn set_cursor_visible(&mut self, visible: bool) {
+        tracing::warn!(
+            "set_cursor_visible is not supported by this window backend. ignoring {visible}"
+        );
+    }
+    /// returns the raw (unaccelerated where supported) relative mouse motion accumulated since the last call and resets it.
+    /// unlike cursor position, this keeps reporting motion even when the cursor is locked with `Self::set_cursor_grab`.
+    /// units are unspecified and depend on the platform / device.
+    fn take_raw_mouse_delta(&mut self) -> [f64; 2] {
+        [0.0; 2]
+    }
+    /// the relative mouse motion (same units as `Self::take_raw_mouse_delta`) between the previous and the current
+    /// `Self::take_raw_input`. it doesn't reset when read, so any code in `UserAppData::run` can use it. eg: for infinite
+    /// drag sliders, which need motion even when the cursor is stuck at the edge of the screen.
+    /// egui 0.20 has no event for relative motion, so it isn't pushed into egui's input.
+    fn frame_mouse_delta(&self) -> [f64; 2] {
+        [0.0; 2]
+    }
+    /// mutable access to the input which will be given to egui in the next frame.
+    /// `None` if the backend doesn't expose it, in which case `Self::push_event` and `Self::push_raw_input` do nothing.
+    fn raw_input_mut(&mut self) -> Option<&mut RawInput> {
+        None
+    }
+    /// injects a synthetic event into the next frame's input. useful for scripted input in tests (type text, click at coords etc..).
+    fn push_event(&mut self, event: egui::Event) {
+        match self.raw_input_mut() {
+            Some(raw_input) => raw_input.events.push(event),
+            None => tracing::warn!(
+                "this window backend doesn't support pushing input. ignoring {event:?}"
+            ),
+        }
+    }
+    /// merges the provided input into the next frame's input using `RawInput::append`.
+    /// events and files are appended, while `Some` fields like `screen_rect` replace the existing values.
+    fn push_raw_input(&mut self, raw_input: RawInput) {
+        match self.raw_input_mut() {
+            Some(next_raw_input) => next_raw_input.append(raw_input),
+            None => tracing::warn!(
+                "this window backend doesn't support pushing input. ignoring raw input"
+            ),
+        }
+    }
+    /// physical pixels per logical pixel for each axis. this includes `Self::set_scale_factor_override`, but ignores
+    /// `Self::set_virtual_screen_size` (see `Letterbox` for that). window backends use this (through `Self::physical_to_logical`
+    /// and `Self::logical_to_physical`) for all their dpi conversions. most platforms use the same value for both axes.
+    /// backends which don't know their scale factor return 1.0, so the conversions leave values unchanged.
+    fn scale_factor(&self) -> [f32; 2] {
+        [1.0; 2]
+    }
+    /// converts a position or size in physical pixels into logical pixels (points) using `Self::scale_factor`
+    fn physical_to_logical(&self, physical: [f32; 2]) -> [f32; 2] {
+        let scale = self.scale_factor();
+        [physical[0] / scale[0], physical[1] / scale[1]]
+    }
+    /// converts a position or size in logical pixels (points) into physical pixels using `Self::scale_factor`
+    fn logical_to_physical(&self, logical: [f32; 2]) -> [f32; 2] {
+        let scale = self.scale_factor();
+        [logical[0] * scale[0], logical[1] * scale[1]]
+    }
+    /// overrides the OS provided scale factor (`RawInput::pixels_per_point`) with the provided value.
+    /// useful for accessibility zoom or testing. `None` goes back to using the scale factor of the monitor,
+    /// and keeps following it when the window moves between monitors with different dpi.
+    fn set_scale_factor_override(&mut self, scale: Option<f32>) {
+        tracing::warn!(
+            "set_scale_factor_override is not supported by this window backend. ignoring {scale:?}"
+        );
+    }
+    /// pins the logical screen size (`RawInput::screen_rect`) to the provided size, regardless of the window size.
+    /// egui is scaled to fit the window and centered with letterbox bars (see `Letterbox`), and pointer positions are
+    /// transformed back into the virtual screen space. useful for kiosks or games with a fixed ui resolution.
+    /// `None` goes back to following the window size. the bars are cleared with the gfx backend's clear color.
+    /// overrides `Self::set_scale_factor_override` while set.
+    fn set_virtual_screen_size(&mut self, size: Option<[f32; 2]>) {
+        tracing::warn!(
+            "set_virtual_screen_size is not supported by this window backend. ignoring {size:?}"
+        );
+    }
+    /// show or hide the window's decorations (title bar, borders). useful for borderless windows with a custom title bar.
+    /// some platforms (eg: web, android) ignore this.
+    fn set_decorations(&mut self, decorations: bool) {
+        tracing::warn!(
+            "set_decorations is not supported by this window backend. ignoring {decorations}"
+        );
+    }
+    /// whether the user can resize the window. some platforms ignore this.
+    fn set_resizable(&mut self, resizable: bool) {
+        tracing::warn!(
+            "set_resizable is not supported by this window backend. ignoring {resizable}"
+        );
+    }
+    /// keep the window visible on all workspaces / virtual desktops (sticky), eg: for overlays. inherently platform specific.
+    /// the glfw backend supports it on linux with x11 (using `_NET_WM_STATE_STICKY`) and only warns elsewhere, as windows and
+    /// mac don't have a public api for it.
+    fn set_sticky(&mut self, sticky: bool) {
+        tracing::warn!("set_sticky is not supported by this window backend. ignoring {sticky}");
+    }
+    /// minimum size of the window's client area in logical pixels (converted with `Self::logical_to_physical` where needed).
+    /// `None` removes the limit. useful to prevent the ui from being resized into an unusable state.
+    fn set_min_inner_size(&mut self, size: Option<[f32; 2]>) {
+        tracing::warn!(
+            "set_min_inner_size is not supported by this window backend. ignoring {size:?}"
+        );
+    }
+    /// maximum size of the window's client area in logical pixels. `None` removes the limit.
+    fn set_max_inner_size(&mut self, size: Option<[f32; 2]>) {
+        tracing::warn!(
+            "set_max_inner_size is not supported by this window backend. ignoring {size:?}"
+        );
+    }
     /// get openGL function addresses. optional, just like `Self::swap_buffers`.
     /// panic! if it doesn't apply to your WindowBackend. eg: winit.
     fn get_proc_address(&mut self, symbol: &str) -> *const core::ffi::c_void {
@@ -147,6 +452,8 @@ pub trait GfxBackend<W: WindowBackend> {
 
     /// Android only. callend on app suspension, which destroys the window.
     /// so, will need to destroy the `Surface` and recreate during resume event.
+    /// window backends also call this (without destroying the window) when they are configured to suspend after
+    /// some time without input (eg: `auto_suspend_after` of the winit / glfw configs).
     fn suspend(&mut self, _window_backend: &mut W) {
         unimplemented!("This window backend doesn't implement suspend event");
     }
@@ -167,6 +474,16 @@ pub trait GfxBackend<W: WindowBackend> {
     /// on opengl, you might call `WindowBackend::swap_buffers`.
     /// on wgpu / vulkan, you might submit commands to queues, present swapchain image etc..
     fn present(&mut self, window_backend: &mut W);
+
+    /// called instead of `Self::render` and `Self::present` when the window backend skips a frame, because it would look
+    /// exactly like the previous one (see `FrameDirtyTracker`). there are no texture updates in skipped frames.
+    /// release whatever `Self::prepare_frame` acquired (eg: the swapchain image) *without* showing it, so that the previous
+    /// frame stays on screen and the swapchain doesn't run out of images.
+    /// returns false if the frame can't be discarded (the default), in which case the window backend renders and presents
+    /// it as usual.
+    fn discard_frame(&mut self, _window_backend: &mut W) -> bool {
+        false
+    }
 }
 
 /// This is the trait most users care about. just implement this trait and you can use any `WindowBackend` or `GfxBackend` to run your egui app.
@@ -211,6 +528,8 @@ pub trait UserAppData<W: WindowBackend, G: GfxBackend<W>> {
     /// you can use the rawinput to get events like cursor movement, button presses/releases, keyboard key press/releases, window resize events etc.
     /// and you can filter them out too. like only restricting egui to left half of your window by modifying the resize event before starting egui context.
     /// you can also use the fulloutput to add accesskit or other useful features without support from windowing/gfx backends.
+    /// window backends only use the cursor icon, copied text and open url from `PlatformOutput`. other things like
+    /// `PlatformOutput::events` are just logged and dropped. so, inspect them before returning if you want to react to them.
     fn run(
         &mut self,
         egui_context: &egui::Context,
@@ -218,4 +537,191 @@ pub trait UserAppData<W: WindowBackend, G: GfxBackend<W>> {
         window_backend: &mut W,
         gfx_backend: &mut G,
     ) -> egui::FullOutput;
+    /// called by the window backend after every frame (after `Self::run`).
+    /// return `AppControlFlow::Exit` to stop the event loop or `AppControlFlow::Error` to stop and propagate an error.
+    /// default impl always continues.
+    fn control_flow(&mut self) -> AppControlFlow {
+        AppControlFlow::Continue
+    }
+    /// called by the window backend when the user asks to close the window (eg: the close button of the title bar or alt + F4).
+    /// return false to keep the window open. eg: to show an "unsaved changes, quit anyway?" dialog in the next frames, and exit
+    /// with `AppControlFlow::Exit` from `Self::control_flow` once the user confirms.
+    /// default impl returns true, which closes the window.
+    fn on_close_requested(&mut self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_wide_window_gets_bars_on_the_sides() {
+        let letterbox = Letterbox::new([800.0, 600.0], [1000, 600]);
+        assert_eq!(letterbox.pixels_per_point, 1.0);
+        assert_eq!(letterbox.viewport, [100.0, 0.0, 800.0, 600.0]);
+        // corners of the viewport map to the corners of the virtual screen
+        assert_eq!(
+            letterbox.physical_to_virtual([100.0, 0.0]),
+            egui::pos2(0.0, 0.0)
+        );
+        assert_eq!(
+            letterbox.physical_to_virtual([900.0, 600.0]),
+            egui::pos2(800.0, 600.0)
+        );
+        // clicks on the bars are outside of the virtual screen, so egui ignores them
+        assert_eq!(letterbox.physical_to_virtual([50.0, 300.0]).x, -50.0);
+        assert_eq!(letterbox.physical_to_virtual([950.0, 300.0]).x, 850.0);
+    }
+
+    #[test]
+    fn letterbox_tall_window_scales_up_with_bars_on_top_and_bottom() {
+        // width allows a scale of 2, height would allow 2.66. the smaller one wins to keep the aspect ratio
+        let letterbox = Letterbox::new([400.0, 300.0], [800, 800]);
+        assert_eq!(letterbox.pixels_per_point, 2.0);
+        assert_eq!(letterbox.viewport, [0.0, 100.0, 800.0, 600.0]);
+        assert_eq!(
+            letterbox.physical_to_virtual([400.0, 400.0]),
+            egui::pos2(200.0, 150.0)
+        );
+    }
+
+    #[test]
+    fn letterbox_of_minimized_window_has_a_usable_scale() {
+        let letterbox = Letterbox::new([800.0, 600.0], [0, 0]);
+        assert!(letterbox.pixels_per_point > 0.0);
+        let position = letterbox.physical_to_virtual([0.0, 0.0]);
+        assert!(position.x.is_finite() && position.y.is_finite());
+    }
+
+    #[test]
+    fn letterbox_virtual_to_physical_is_the_inverse() {
+        let letterbox = Letterbox::new([400.0, 300.0], [800, 800]);
+        let position = egui::pos2(123.0, 45.0);
+        assert_eq!(letterbox.virtual_to_physical(position), [246.0, 190.0]);
+        assert_eq!(
+            letterbox.physical_to_virtual(letterbox.virtual_to_physical(position)),
+            position
+        );
+        // a widget rect in virtual space, offset by the top bar and scaled by 2
+        assert_eq!(
+            letterbox.virtual_rect_to_physical(egui::Rect::from_min_max(
+                egui::pos2(10.0, 10.0),
+                egui::pos2(20.0, 30.0)
+            )),
+            [20.0, 120.0, 20.0, 40.0]
+        );
+    }
+
+    /// output of a frame where egui doesn't need an immediate repaint
+    fn idle_output(shapes: Vec<egui::epaint::ClippedShape>) -> egui::FullOutput {
+        egui::FullOutput {
+            repaint_after: std::time::Duration::from_secs(1),
+            shapes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_dirty_tracker_skips_unchanged_frames() {
+        let mut tracker = FrameDirtyTracker::default();
+        // the first frame is always drawn
+        assert!(tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 1.0, false));
+        assert!(!tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 1.0, false));
+        // a resize must be drawn even if egui's output is the same, because the surface is reconfigured
+        assert!(tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 1.0, true));
+        assert!(!tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 1.0, false));
+        // scale changes are remembered, so only the first frame with the new scale is dirty
+        assert!(tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 2.0, false));
+        assert!(!tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 2.0, false));
+        assert!(tracker.is_dirty(&idle_output(vec![]), [400.0, 300.0], 2.0, false));
+    }
+
+    #[test]
+    fn frame_dirty_tracker_compares_shapes_with_the_last_drawn_frame() {
+        let shape = egui::epaint::ClippedShape(
+            egui::Rect::EVERYTHING,
+            egui::Shape::circle_filled(egui::pos2(10.0, 10.0), 5.0, egui::Color32::RED),
+        );
+        let moved_shape = egui::epaint::ClippedShape(
+            egui::Rect::EVERYTHING,
+            egui::Shape::circle_filled(egui::pos2(20.0, 10.0), 5.0, egui::Color32::RED),
+        );
+        let mut tracker = FrameDirtyTracker::default();
+        assert!(tracker.is_dirty(
+            &idle_output(vec![shape.clone()]),
+            [800.0, 600.0],
+            1.0,
+            false
+        ));
+        assert!(!tracker.is_dirty(
+            &idle_output(vec![shape.clone()]),
+            [800.0, 600.0],
+            1.0,
+            false
+        ));
+        assert!(tracker.is_dirty(&idle_output(vec![moved_shape]), [800.0, 600.0], 1.0, false));
+        // moving back is a change too
+        assert!(tracker.is_dirty(&idle_output(vec![shape]), [800.0, 600.0], 1.0, false));
+    }
+
+    #[test]
+    fn frame_dirty_tracker_draws_texture_updates_and_animations() {
+        let mut tracker = FrameDirtyTracker::default();
+        assert!(tracker.is_dirty(&idle_output(vec![]), [800.0, 600.0], 1.0, false));
+        // freeing a texture without changing any shape still needs the frame, so that the gfx backend applies the delta
+        let mut output = idle_output(vec![]);
+        output.textures_delta.free.push(egui::TextureId::Managed(1));
+        assert!(tracker.is_dirty(&output, [800.0, 600.0], 1.0, false));
+        let mut output = idle_output(vec![]);
+        output.repaint_after = std::time::Duration::ZERO;
+        assert!(tracker.is_dirty(&output, [800.0, 600.0], 1.0, false));
+        assert!(tracker.is_dirty(&output, [800.0, 600.0], 1.0, false));
+    }
+
+    fn pointer_moved(x: f32) -> egui::Event {
+        egui::Event::PointerMoved(egui::pos2(x, 0.0))
+    }
+
+    #[test]
+    fn push_coalesced_event_keeps_only_the_latest_of_consecutive_moves() {
+        let mut events = Vec::new();
+        for x in [1.0, 2.0, 3.0] {
+            push_coalesced_event(&mut events, pointer_moved(x));
+        }
+        assert_eq!(events, vec![pointer_moved(3.0)]);
+    }
+
+    #[test]
+    fn push_coalesced_event_keeps_the_position_of_a_click() {
+        // the move right before a press decides where egui thinks the click happened, so it must not be merged
+        // with the moves after the press
+        let press = egui::Event::PointerButton {
+            pos: egui::pos2(2.0, 0.0),
+            button: egui::PointerButton::Primary,
+            pressed: true,
+            modifiers: egui::Modifiers::default(),
+        };
+        let mut events = Vec::new();
+        for event in [
+            pointer_moved(1.0),
+            pointer_moved(2.0),
+            press.clone(),
+            pointer_moved(3.0),
+            pointer_moved(4.0),
+            egui::Event::PointerGone,
+        ] {
+            push_coalesced_event(&mut events, event);
+        }
+        assert_eq!(
+            events,
+            vec![
+                pointer_moved(2.0),
+                press,
+                pointer_moved(4.0),
+                egui::Event::PointerGone
+            ]
+        );
+    }
 }
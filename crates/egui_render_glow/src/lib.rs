@@ -203,7 +203,9 @@ impl<W: WindowBackend> GfxBackend<W> for GlowBackend {
     }
 
     fn suspend(&mut self, _window_backend: &mut W) {
-        unimplemented!("glow render backend doesn't support suspend callback yet");
+        // the gl context belongs to the window backend. so, there's nothing to release here.
+        // this keeps window backends' `auto_suspend_after` working. they just stop drawing while suspended.
+        tracing::debug!("suspend does nothing on glow backend");
     }
 
     fn resume(&mut self, _window_backend: &mut W) {
@@ -242,6 +244,12 @@ impl<W: WindowBackend> GfxBackend<W> for GlowBackend {
         }
         // on wasm, there's no swap buffers.. the browser takes care of it automatically.
     }
+
+    /// we just don't swap buffers, so the previous frame stays on screen.
+    /// on wasm, the browser shows the canvas cleared by `GfxBackend::prepare_frame`. so, the frame is rendered as usual on web.
+    fn discard_frame(&mut self, _window_backend: &mut W) -> bool {
+        cfg!(any(not(target_arch = "wasm32"), target_os = "emscripten"))
+    }
 }
 
 #[cfg(all(target_arch = "wasm32", not(target_os = "emscripten")))]
@@ -376,6 +384,7 @@ impl Painter {
             meshes,
             textures_delta,
             screen_size_logical,
+            ..
         } = egui_gfx_output;
         self.textures_to_delete = textures_delta.free;
         self.clipped_primitives = meshes;
@@ -53,4 +53,8 @@ impl<W: WindowBackend> GfxBackend<W> for ThreeDBackend {
     fn present(&mut self, window_backend: &mut W) {
         self.glow_backend.present(window_backend);
     }
+
+    fn discard_frame(&mut self, window_backend: &mut W) -> bool {
+        self.glow_backend.discard_frame(window_backend)
+    }
 }
@@ -4,30 +4,38 @@ use egui::{
     Rect, TextureId,
 };
 use egui_backend::egui;
+use egui_backend::raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use egui_backend::{EguiGfxData, GfxBackend, WindowBackend};
 use intmap::IntMap;
 use std::{
+    collections::HashMap,
     convert::TryInto,
-    num::{NonZeroU32, NonZeroU64},
-    sync::Arc,
+    num::{NonZeroU32, NonZeroU64, NonZeroU8},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 use tracing::{debug, info};
 pub use wgpu;
 use wgpu::{
-    Adapter, AddressMode, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
-    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
-    BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer, BufferBinding,
-    BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites,
-    CommandEncoder, CommandEncoderDescriptor, Device, DeviceDescriptor, Extent3d, FilterMode,
-    FragmentState, FrontFace, ImageCopyTexture, ImageDataLayout, IndexFormat, Instance, Limits,
-    LoadOp, MultisampleState, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode,
-    PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology, Queue, RenderPass,
-    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
-    RequestAdapterOptions, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor,
-    ShaderSource, ShaderStages, Surface, SurfaceConfiguration, SurfaceTexture, Texture,
-    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
-    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexAttribute,
-    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+    Adapter, AdapterInfo, AddressMode, Backend, Backends, BindGroup, BindGroupDescriptor,
+    BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+    BufferBinding, BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState,
+    ColorWrites, CommandEncoder, CommandEncoderDescriptor, CompareFunction, DepthBiasState,
+    DepthStencilState, Device, DeviceDescriptor, Extent3d, Features, FilterMode, FragmentState,
+    FrontFace, ImageCopyTexture, ImageDataLayout, IndexFormat, Instance, Limits, LoadOp, Maintain,
+    MultisampleState, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode, PowerPreference,
+    PresentMode, PrimitiveState, PrimitiveTopology, PushConstantRange, Queue, RenderPass,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilFaceState,
+    StencilState, Surface, SurfaceConfiguration, SurfaceTexture, Texture, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexState, VertexStepMode,
 };
 
 /// This provides a Gfx backend for egui by implementing the `crate::GfxBackend` trait.
@@ -61,14 +69,95 @@ pub struct WgpuBackend {
     /// `wgpu::Queue::submit` is very expensive, so we will submit ALL command encoders at the same time during the `present_frame` method
     /// just before presenting the swapchain image (surface texture).
     pub command_encoders: Vec<CommandEncoder>,
+    /// if `Some`, the egui render pass clears the surface with this color before drawing egui. otherwise, egui is drawn
+    /// on top of whatever is already in the surface (eg: your own rendering in `UserAppData::run`).
+    /// set with `Self::set_clear_color`
+    clear_color: Option<wgpu::Color>,
+    /// validation errors reported to the uncaptured error handler of the device. shared with the handler.
+    /// drained by `Self::poll_errors`
+    captured_errors: Arc<std::sync::Mutex<Vec<wgpu::Error>>>,
+    /// if false, we don't acquire / draw / present the surface texture. set with `Self::set_rendering_enabled`
+    rendering_enabled: bool,
+    /// if true, `GfxBackend::present` blocks on `Device::poll` after submitting. set with `Self::set_poll_after_submit`
+    poll_after_submit: bool,
+    /// set by the uncaptured error handler of the device when the device is lost, or by `GfxBackend::prepare_frame`
+    /// when the surface is still lost after reconfiguration.
+    /// see `Self::device_lost`
+    device_lost: Arc<AtomicBool>,
+    /// size of the surface when `GfxBackend::prepare_frame` last configured it. during a live resize, window backends report
+    /// a resize for almost every frame. if the size didn't actually change, we skip the (expensive) reconfiguration.
+    last_configured_size: [u32; 2],
+    /// painters for additional egui contexts, drawn on top of the main egui context. see `Self::add_layer`
+    layers: Vec<EguiPainter>,
+    /// layers queued with `Self::queue_layer` for the current frame, in draw order
+    queued_layers: Vec<(usize, EguiGfxData)>,
+    /// usages of `Self::depth_stencil`. taken from `WgpuConfig::depth_stencil_usage`
+    depth_stencil_usage: TextureUsages,
+    /// depth-stencil attachment of the egui render pass, if the painter has a depth-stencil format.
+    /// (re)created lazily with the size of the render target. see `Self::depth_stencil_view`
+    depth_stencil: Option<DepthStencilTarget>,
+    /// taken from `WgpuConfig::on_surface_format_changed`. set with `Self::set_on_surface_format_changed`
+    on_surface_format_changed: Option<Box<dyn FnMut(&Device, TextureFormat)>>,
+}
+
+/// the depth-stencil texture of the egui render pass and what it was created with
+struct DepthStencilTarget {
+    texture: Texture,
+    view: TextureView,
+    size: [u32; 2],
+    format: TextureFormat,
 }
 
 pub struct WgpuConfig {
-    backends: Backends,
-    power_preference: PowerPreference,
-    device_descriptor: DeviceDescriptor<'static>,
-    surface_formats_priority: Vec<TextureFormat>,
-    surface_config: SurfaceConfiguration,
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    pub device_descriptor: DeviceDescriptor<'static>,
+    pub surface_formats_priority: Vec<TextureFormat>,
+    pub surface_config: SurfaceConfiguration,
+    /// anisotropic filtering level for the linear sampler used by egui textures. valid values: 1, 2, 4, 8 and 16.
+    /// if the adapter doesn't support anisotropic filtering or the value is invalid, we will warn and use `None` instead.
+    pub anisotropy_clamp: Option<NonZeroU8>,
+    /// request a software / fallback adapter. useful on headless CI machines without a gpu.
+    pub force_fallback_adapter: bool,
+    /// if set, we will enumerate all adapters and choose the first one for which this returns true (and which is compatible with the surface).
+    /// eg: exclude integrated gpus or require a specific vendor.
+    /// on wasm, adapters can't be enumerated. so, we will just warn if the requested adapter doesn't pass the filter.
+    pub adapter_filter: Option<Box<dyn Fn(&AdapterInfo) -> bool>>,
+    /// if set, wgpu will record an api trace into this directory, which can be replayed to debug gpu issues.
+    /// requires the `trace` feature of wgpu. otherwise, wgpu will just log an error and ignore it.
+    pub trace_path: Option<PathBuf>,
+    /// wgsl source used instead of `EGUI_SHADER_SRC` to draw egui. eg: to add dithering or a colorblind filter.
+    /// it must keep the bindings and entry points of `EGUI_SHADER_SRC`. see `validate_egui_shader_src`.
+    /// if it is invalid, we will warn and use `EGUI_SHADER_SRC` instead.
+    pub custom_shader_src: Option<String>,
+    /// if set, this is called with the chosen adapter and the returned descriptor is used instead of `Self::device_descriptor`.
+    /// useful to request optional features only when the adapter supports them, or to raise limits up to the adapter's limits.
+    /// eg: `Box::new(|adapter| DeviceDescriptor { features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY, .. })`
+    pub device_descriptor_callback: Option<Box<dyn Fn(&Adapter) -> DeviceDescriptor<'static>>>,
+    /// wait for the gpu with `Device::poll(Maintain::Wait)` after submitting in `GfxBackend::present`. default is false.
+    /// presenting the surface usually drives the queue. enable this if you rely on mapped buffer callbacks (eg: readback)
+    /// firing every frame. it stalls the cpu until the gpu is done, so it adds latency. see `WgpuBackend::poll`
+    pub poll_after_submit: bool,
+    /// pass egui's screen size to the vertex shader as a push constant instead of a uniform buffer. default is false.
+    /// if the adapter supports `Features::PUSH_CONSTANTS`, we request it (and a `max_push_constant_size` of at least 16 bytes).
+    /// otherwise, we warn and keep using the uniform buffer. see `EguiPainter::set_screen_size_push_constants`
+    pub screen_size_push_constants: bool,
+    /// if set, the egui render pass gets a depth-stencil attachment of this format (eg: `Depth24PlusStencil8`), which is
+    /// cleared every frame. paint callbacks can use it for stencil masking (portals, non rectangular clipping) or depth testing.
+    /// egui's own draws neither test nor write depth / stencil. default is `None`, which doesn't allocate anything.
+    /// see `EguiPainter::set_depth_stencil_format`
+    pub depth_stencil_format: Option<TextureFormat>,
+    /// usages of the depth-stencil texture, in addition to `RENDER_ATTACHMENT`. eg: `TEXTURE_BINDING` to sample it later
+    pub depth_stencil_usage: TextureUsages,
+    /// write egui's vertices and indices directly into host visible buffers instead of copying them from a staging buffer.
+    /// if the adapter supports `Features::MAPPABLE_PRIMARY_BUFFERS`, we request it. otherwise, we warn and keep the staging copy.
+    /// only enable this on integrated gpus. default is false. see `EguiPainter::set_mappable_mesh_buffers`
+    pub mappable_mesh_buffers: bool,
+    /// called with the device and the new format when a resume picks a different surface format than before
+    /// (eg: the window moved to a monitor with a different surface). the painter recreates its pipelines by itself.
+    /// use this to recreate your own pipelines (eg: for paint callbacks) which must match the surface format.
+    /// see `WgpuBackend::surface_format` and `WgpuBackend::set_on_surface_format_changed`
+    pub on_surface_format_changed: Option<Box<dyn FnMut(&Device, TextureFormat)>>,
 }
 impl Default for WgpuConfig {
     fn default() -> Self {
@@ -92,11 +181,438 @@ impl Default for WgpuConfig {
                 TextureFormat::Bgra8UnormSrgb,
                 TextureFormat::Rgba8UnormSrgb,
             ],
+            anisotropy_clamp: None,
+            force_fallback_adapter: false,
+            adapter_filter: None,
+            trace_path: None,
+            custom_shader_src: None,
+            device_descriptor_callback: None,
+            poll_after_submit: false,
+            screen_size_push_constants: false,
+            depth_stencil_format: None,
+            depth_stencil_usage: TextureUsages::RENDER_ATTACHMENT,
+            mappable_mesh_buffers: false,
+            on_surface_format_changed: None,
+        }
+    }
+}
+
+impl WgpuConfig {
+    /// start building a config from the default values
+    pub fn builder() -> WgpuConfigBuilder {
+        WgpuConfigBuilder::default()
+    }
+}
+
+/// fluent builder for `WgpuConfig`. all fields start with their `WgpuConfig::default()` values.
+/// `Self::build` makes sure that the invariants we rely on (eg: surface usage contains `RENDER_ATTACHMENT`) are upheld.
+#[derive(Default)]
+pub struct WgpuConfigBuilder {
+    config: WgpuConfig,
+}
+
+impl WgpuConfigBuilder {
+    pub fn backends(mut self, backends: Backends) -> Self {
+        self.config.backends = backends;
+        self
+    }
+    pub fn power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.config.power_preference = power_preference;
+        self
+    }
+    /// the present mode decides the vsync behavior and the frame latency (see `WgpuBackend::approximate_frame_latency`).
+    /// wgpu 0.14 doesn't let us choose the swapchain image count / maximum frame latency. so, this is the only knob.
+    /// for low latency overlays, prefer `Mailbox` (no tearing, latest frame wins) or `Immediate` (tearing) over `Fifo`.
+    /// unsupported modes fall back to `Fifo` (with a warning). `AutoVsync` / `AutoNoVsync` pick the best supported mode.
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.config.surface_config.present_mode = present_mode;
+        self
+    }
+    /// texture usages of the surface, in addition to `RENDER_ATTACHMENT` (always added by `Self::build`).
+    /// `WgpuBackend::read_rect` and `WgpuBackend::frame_reader` need `TextureUsages::COPY_SRC`.
+    /// usages which the surface (probably) doesn't support are removed with a warning. see `WgpuBackend::surface_usages`.
+    pub fn surface_usage(mut self, usage: TextureUsages) -> Self {
+        self.config.surface_config.usage = usage;
+        self
+    }
+    /// surface formats in the order of priority. the first one supported by the surface will be used.
+    pub fn surface_formats(mut self, surface_formats: Vec<TextureFormat>) -> Self {
+        self.config.surface_formats_priority = surface_formats;
+        self
+    }
+    /// features required from the device
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.config.device_descriptor.features = features;
+        self
+    }
+    /// limits required from the device
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.config.device_descriptor.limits = limits;
+        self
+    }
+    pub fn anisotropy_clamp(mut self, anisotropy_clamp: Option<NonZeroU8>) -> Self {
+        self.config.anisotropy_clamp = anisotropy_clamp;
+        self
+    }
+    pub fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.config.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+    pub fn adapter_filter(
+        mut self,
+        adapter_filter: impl Fn(&AdapterInfo) -> bool + 'static,
+    ) -> Self {
+        self.config.adapter_filter = Some(Box::new(adapter_filter));
+        self
+    }
+    /// how the surface is composited with the windows behind it. only matters for transparent windows (eg: overlays).
+    /// egui's pipeline outputs premultiplied alpha (see `EGUI_PIPELINE_BLEND_STATE`), so transparent overlays should use
+    /// `CompositeAlphaMode::PreMultiplied` where it's supported (usually vulkan on X11 / Wayland and dx12 / vulkan on windows).
+    /// `Auto` often picks `Opaque` / `Inherit`, which causes dark halos or no transparency at all.
+    /// if the surface doesn't support the chosen mode, we warn and use the first supported one. see `WgpuBackend::surface_capabilities`.
+    pub fn alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> Self {
+        self.config.surface_config.alpha_mode = alpha_mode;
+        self
+    }
+    pub fn trace_path(mut self, trace_path: Option<PathBuf>) -> Self {
+        self.config.trace_path = trace_path;
+        self
+    }
+    pub fn custom_shader_src(mut self, custom_shader_src: Option<String>) -> Self {
+        self.config.custom_shader_src = custom_shader_src;
+        self
+    }
+    pub fn device_descriptor_callback(
+        mut self,
+        device_descriptor_callback: impl Fn(&Adapter) -> DeviceDescriptor<'static> + 'static,
+    ) -> Self {
+        self.config.device_descriptor_callback = Some(Box::new(device_descriptor_callback));
+        self
+    }
+    pub fn poll_after_submit(mut self, poll_after_submit: bool) -> Self {
+        self.config.poll_after_submit = poll_after_submit;
+        self
+    }
+    pub fn screen_size_push_constants(mut self, screen_size_push_constants: bool) -> Self {
+        self.config.screen_size_push_constants = screen_size_push_constants;
+        self
+    }
+    pub fn depth_stencil_format(mut self, depth_stencil_format: Option<TextureFormat>) -> Self {
+        self.config.depth_stencil_format = depth_stencil_format;
+        self
+    }
+    pub fn depth_stencil_usage(mut self, depth_stencil_usage: TextureUsages) -> Self {
+        self.config.depth_stencil_usage = depth_stencil_usage;
+        self
+    }
+    pub fn mappable_mesh_buffers(mut self, mappable_mesh_buffers: bool) -> Self {
+        self.config.mappable_mesh_buffers = mappable_mesh_buffers;
+        self
+    }
+    pub fn on_surface_format_changed(
+        mut self,
+        on_surface_format_changed: impl FnMut(&Device, TextureFormat) + 'static,
+    ) -> Self {
+        self.config.on_surface_format_changed = Some(Box::new(on_surface_format_changed));
+        self
+    }
+    pub fn build(mut self) -> WgpuConfig {
+        // we always render egui into the surface
+        self.config.surface_config.usage |= TextureUsages::RENDER_ATTACHMENT;
+        if let Some(format) = self.config.surface_formats_priority.first() {
+            self.config.surface_config.format = *format;
+        }
+        self.config
+    }
+}
+
+/// the options of `WgpuConfig` which don't depend on creating the instance, adapter or device. used by `WgpuBackend::from_existing`.
+/// the fields are documented in `WgpuConfig`. default values are the same too.
+pub struct ExistingDeviceConfig {
+    pub surface_formats_priority: Vec<TextureFormat>,
+    pub surface_config: SurfaceConfiguration,
+    pub anisotropy_clamp: Option<NonZeroU8>,
+    pub custom_shader_src: Option<String>,
+    pub poll_after_submit: bool,
+    /// only enabled if the device was created with `Features::PUSH_CONSTANTS` and a big enough `max_push_constant_size`
+    pub screen_size_push_constants: bool,
+    pub depth_stencil_format: Option<TextureFormat>,
+    pub depth_stencil_usage: TextureUsages,
+    /// only enabled if the device was created with `Features::MAPPABLE_PRIMARY_BUFFERS`
+    pub mappable_mesh_buffers: bool,
+    pub on_surface_format_changed: Option<Box<dyn FnMut(&Device, TextureFormat)>>,
+}
+impl Default for ExistingDeviceConfig {
+    fn default() -> Self {
+        let WgpuConfig {
+            surface_formats_priority,
+            surface_config,
+            anisotropy_clamp,
+            custom_shader_src,
+            poll_after_submit,
+            screen_size_push_constants,
+            depth_stencil_format,
+            depth_stencil_usage,
+            mappable_mesh_buffers,
+            on_surface_format_changed,
+            ..
+        } = WgpuConfig::default();
+        Self {
+            surface_formats_priority,
+            surface_config,
+            anisotropy_clamp,
+            custom_shader_src,
+            poll_after_submit,
+            screen_size_push_constants,
+            depth_stencil_format,
+            depth_stencil_usage,
+            mappable_mesh_buffers,
+            on_surface_format_changed,
         }
     }
 }
 
 impl WgpuBackend {
+    /// reads back a rectangle `[x, y, width, height]` (in physical pixels) of the current surface texture as tightly packed RGBA8 rows.
+    /// useful for things like "copy this chart to clipboard". to read every frame, use `Self::frame_reader` instead.
+    ///
+    /// the surface must be created with `TextureUsages::COPY_SRC` (see `WgpuConfigBuilder::surface_usage`) and the surface texture
+    /// only exists between `GfxBackend::prepare_frame` and `GfxBackend::present`. any pending `Self::command_encoders` are submitted first,
+    /// so to capture egui, call this after `GfxBackend::render` (eg: when you are driving the frame yourself).
+    /// the rect is clamped to the surface size. blocks until the copy is finished. not supported on wasm.
+    ///
+    /// returns `None` if the surface texture is not acquired, the surface doesn't have `COPY_SRC` usage or the clamped rect is empty.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_rect(&mut self, rect: [u32; 4]) -> Option<Vec<u8>> {
+        let x = rect[0].min(self.surface_config.width);
+        let y = rect[1].min(self.surface_config.height);
+        let width = rect[2].min(self.surface_config.width - x);
+        let height = rect[3].min(self.surface_config.height - y);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let padded_bytes_per_row = padded_bytes_per_row(width);
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("read rect buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        if !self.copy_surface_to_buffer(&buffer, [x, y, width, height], padded_bytes_per_row) {
+            return None;
+        }
+        let mut pixels = Vec::new();
+        read_padded_buffer(
+            &self.device,
+            &buffer,
+            width * 4,
+            padded_bytes_per_row,
+            is_bgra(self.surface_config.format),
+            &mut pixels,
+        );
+        Some(pixels)
+    }
+    /// creates a `FrameReader` sized to the current surface, which can be reused to read back every frame (eg: screen recording).
+    /// unlike `Self::read_rect`, it never blocks the render thread. same requirements as `Self::read_rect`. not supported on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn frame_reader(&self) -> FrameReader {
+        FrameReader::new(
+            &self.device,
+            [self.surface_config.width, self.surface_config.height],
+        )
+    }
+    /// copies the rect `[x, y, width, height]` of the current surface texture into the buffer and submits it
+    /// along with the pending command encoders. the rect must be within the surface.
+    /// returns false (and copies nothing) if the surface texture is not acquired or the surface doesn't have `COPY_SRC` usage.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn copy_surface_to_buffer(
+        &mut self,
+        buffer: &Buffer,
+        rect: [u32; 4],
+        padded_bytes_per_row: u32,
+    ) -> bool {
+        if !self.surface_config.usage.contains(TextureUsages::COPY_SRC) {
+            tracing::warn!("surface must have COPY_SRC usage to read from it");
+            return false;
+        }
+        let surface_texture = match self.surface_current_image.as_ref() {
+            Some(surface_image) => &surface_image.texture,
+            None => {
+                tracing::warn!(
+                    "surface texture is only available between prepare_frame and present"
+                );
+                return false;
+            }
+        };
+        let [x, y, width, height] = rect;
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("surface readback command encoder"),
+            });
+        command_encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: surface_texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(
+            std::mem::take(&mut self.command_encoders)
+                .into_iter()
+                .chain(std::iter::once(command_encoder))
+                .map(|encoder| encoder.finish()),
+        );
+        true
+    }
+    /// pauses (or resumes) rendering without destroying the surface. unlike suspend, resuming is instant.
+    /// while disabled, we don't acquire a surface texture, so `Self::surface_view` is always `None` during `UserAppData::run`.
+    /// egui output is still consumed (texture uploads / frees) so that egui state doesn't desync,
+    /// and `Self::command_encoders` are still submitted. window backends keep handling input as usual.
+    pub fn set_rendering_enabled(&mut self, enabled: bool) {
+        self.rendering_enabled = enabled;
+    }
+    /// queries the formats, present modes and alpha modes supported by the window surface.
+    /// useful for a graphics settings ui, to only show the valid options. returns `None` if there's no surface (eg: suspended on android).
+    /// wgpu 0.14 doesn't tell us the supported texture usages. see `Self::surface_usages` instead.
+    pub fn surface_capabilities(&self) -> Option<SurfaceCapabilities> {
+        self.surface.as_ref().map(|surface| SurfaceCapabilities {
+            formats: surface.get_supported_formats(&self.adapter),
+            present_modes: surface.get_supported_present_modes(&self.adapter),
+            alpha_modes: surface.get_supported_alpha_modes(&self.adapter),
+        })
+    }
+    /// polls the device. `Maintain::Poll` just fires the callbacks (eg: `BufferSlice::map_async`) of finished work,
+    /// `Maintain::Wait` blocks until all submitted work is done. returns true if the queue is empty.
+    /// useful in headless setups where there's no surface present to drive the queue. no-op on the web.
+    pub fn poll(&self, maintain: Maintain) -> bool {
+        self.device.poll(maintain)
+    }
+    /// tears down the backend deterministically (eg: when embedding it into a long-lived host process) instead of leaving
+    /// it to the drop order of the fields. unsubmitted `Self::command_encoders` and the current swapchain image are dropped,
+    /// then we wait for all submitted work with `Maintain::Wait`, and drop the egui resources (layers, painter, depth-stencil)
+    /// before the surface and finally the device / queue. returns once the gpu is idle.
+    ///
+    /// the device and queue are `Arc`s. if you cloned them (or created a `SecondarySurface`), they will only be destroyed
+    /// once those clones are dropped too.
+    pub fn shutdown(self) {
+        let Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            painter,
+            surface,
+            surface_current_image,
+            surface_view,
+            command_encoders,
+            layers,
+            queued_layers,
+            depth_stencil,
+            ..
+        } = self;
+        // nothing recorded into these will be submitted anymore
+        drop(command_encoders);
+        drop(queued_layers);
+        drop(surface_view);
+        drop(surface_current_image);
+        // also fires pending map callbacks (eg: of the texture upload belt), which hold references to buffers
+        device.poll(Maintain::Wait);
+        drop(layers);
+        drop(painter);
+        drop(depth_stencil);
+        drop(surface);
+        // destroyed resources are only freed once the device is maintained again
+        device.poll(Maintain::Wait);
+        drop(queue);
+        drop(device);
+        drop(adapter);
+        drop(instance);
+        info!("wgpu backend shut down");
+    }
+    /// see `WgpuConfig::poll_after_submit`
+    pub fn set_poll_after_submit(&mut self, poll_after_submit: bool) {
+        self.poll_after_submit = poll_after_submit;
+    }
+    /// drains the validation errors reported by the device since the last call (they are also logged as they happen).
+    /// wgpu reports them asynchronously. so, an error caused by a frame might only show up after a later frame.
+    /// useful to show gpu errors in a debug ui or to fail tests.
+    pub fn poll_errors(&mut self) -> Vec<wgpu::Error> {
+        std::mem::take(
+            &mut *self
+                .captured_errors
+                .lock()
+                .expect("failed to lock captured errors"),
+        )
+    }
+    /// true if the device was lost (eg: driver crash / reset, gpu removed). once lost, the device stays unusable.
+    /// while lost, we stop acquiring, drawing and presenting frames (and drop `Self::command_encoders`) instead of panicking.
+    ///
+    /// to recover, check this after every frame (eg: in `UserAppData::control_flow` or `UserAppData::run`), drop every
+    /// wgpu resource you created with the old device and create a new backend with `GfxBackend::new` (or `Self::new_async`)
+    /// using the same window backend. egui textures are uploaded again by egui, as long as you also create a new `egui::Context`
+    /// (or call `egui::Context::set_fonts` to force a font texture upload). your own textures must be registered again.
+    pub fn device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+    /// replaces wgpu's default uncaptured error handler (which panics on every error) with one that sets the returned flag
+    /// when the device is lost and collects validation errors for `Self::poll_errors`. out of memory errors are only logged,
+    /// as the device is still usable. unlike error scopes, this doesn't need to block on a future every frame (which never
+    /// resolves on wasm), so it works the same on all platforms.
+    fn register_error_handler(
+        device: &Device,
+    ) -> (Arc<AtomicBool>, Arc<std::sync::Mutex<Vec<wgpu::Error>>>) {
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let captured_errors: Arc<std::sync::Mutex<Vec<wgpu::Error>>> = Default::default();
+        let flag = device_lost.clone();
+        let errors = captured_errors.clone();
+        device.on_uncaptured_error(move |e| {
+            if is_device_lost_error(&e) {
+                tracing::error!("wgpu device lost: {e}");
+                flag.store(true, Ordering::Relaxed);
+            } else if let wgpu::Error::OutOfMemory { .. } = e {
+                tracing::error!("wgpu out of memory: {e}");
+            } else {
+                tracing::error!("wgpu validation error: {e}");
+                errors
+                    .lock()
+                    .expect("failed to lock captured errors")
+                    .push(e);
+            }
+        });
+        (device_lost, captured_errors)
+    }
+    /// `Some(color)` makes the egui render pass clear the surface with the color before drawing egui (`LoadOp::Clear`).
+    /// `None` (default) draws egui on top of the existing contents (`LoadOp::Load`). this stays until you change it again.
+    /// don't set this if you render your own stuff into the surface view before egui, as it will be cleared.
+    pub fn set_clear_color(&mut self, clear_color: Option<wgpu::Color>) {
+        self.clear_color = clear_color;
+    }
+    /// lists the adapters (gpus) available for the given backends, without creating a backend.
+    /// useful for settings ui to let the user pick a gpu. the user's choice can be used in `WgpuConfig::adapter_filter`
+    /// (eg: by comparing `AdapterInfo::name` and `AdapterInfo::backend`).
+    /// not available on wasm, as browsers don't allow enumerating adapters.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn enumerate_adapters(backends: Backends) -> Vec<AdapterInfo> {
+        Instance::new(backends)
+            .enumerate_adapters(backends)
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
     pub async fn new_async<W: WindowBackend>(
         window_backend: &mut W,
         config: <Self as GfxBackend<W>>::Configuration,
@@ -107,35 +623,105 @@ impl WgpuBackend {
             surface_formats_priority,
             mut surface_config,
             backends,
+            anisotropy_clamp,
+            force_fallback_adapter,
+            adapter_filter,
+            trace_path,
+            custom_shader_src,
+            device_descriptor_callback,
+            poll_after_submit,
+            screen_size_push_constants,
+            depth_stencil_format,
+            depth_stencil_usage,
+            mappable_mesh_buffers,
+            on_surface_format_changed,
         } = config;
         debug!("using wgpu backends: {:?}", backends);
         let instance = Arc::new(Instance::new(backends));
-        debug!("iterating over all adapters");
-        #[cfg(target = "wasm32-unknown-unknown")]
-        for adapter in instance.enumerate_adapters(Backends::all()) {
-            debug!("adapter: {:#?}", adapter.get_info());
-        }
         let mut surface = window_backend
             .get_window()
             .map(|w| unsafe { instance.create_surface(w) });
 
         info!("is surfaced created at startup?: {}", surface.is_some());
 
-        debug!("using power preference: {:?}", config.power_preference);
-        let adapter = Arc::new(
+        #[cfg(not(target_arch = "wasm32"))]
+        let filtered_adapter = adapter_filter.as_ref().map(|adapter_filter| {
+            debug!("iterating over all adapters");
             instance
-                .request_adapter(&RequestAdapterOptions {
-                    power_preference: power_preference,
-                    force_fallback_adapter: false,
-                    compatible_surface: surface.as_ref(),
+                .enumerate_adapters(backends)
+                .find(|adapter| {
+                    let info = adapter.get_info();
+                    debug!("adapter: {info:#?}");
+                    (!force_fallback_adapter || info.device_type == wgpu::DeviceType::Cpu)
+                        && surface
+                            .as_ref()
+                            .map(|surface| adapter.is_surface_supported(surface))
+                            .unwrap_or(true)
+                        && adapter_filter(&info)
                 })
-                .await
-                .expect("failed to get adapter"),
-        );
+                .expect("failed to find an adapter which passes the adapter filter")
+        });
+        #[cfg(target_arch = "wasm32")]
+        let filtered_adapter: Option<Adapter> = None;
+
+        let adapter = match filtered_adapter {
+            Some(adapter) => adapter,
+            None => {
+                debug!("using power preference: {:?}", power_preference);
+                let adapter = instance
+                    .request_adapter(&RequestAdapterOptions {
+                        power_preference,
+                        force_fallback_adapter,
+                        compatible_surface: surface.as_ref(),
+                    })
+                    .await
+                    .expect("failed to get adapter");
+                if let Some(adapter_filter) = adapter_filter.as_ref() {
+                    if !adapter_filter(&adapter.get_info()) {
+                        tracing::warn!("requested adapter doesn't pass the adapter filter. using it anyway as we can't enumerate adapters on this platform");
+                    }
+                }
+                adapter
+            }
+        };
+        let adapter = Arc::new(adapter);
 
         info!("chosen adapter details: {:?}", adapter.get_info());
+        let mut device_descriptor = match device_descriptor_callback {
+            Some(device_descriptor_callback) => device_descriptor_callback(&adapter),
+            None => device_descriptor,
+        };
+        let screen_size_push_constants = screen_size_push_constants
+            && {
+                let supported = adapter.features().contains(Features::PUSH_CONSTANTS)
+                    && adapter.limits().max_push_constant_size >= 16;
+                if supported {
+                    device_descriptor.features |= Features::PUSH_CONSTANTS;
+                    device_descriptor.limits.max_push_constant_size =
+                        device_descriptor.limits.max_push_constant_size.max(16);
+                } else {
+                    tracing::warn!("adapter doesn't support push constants. using a uniform buffer for the screen size");
+                }
+                supported
+            };
+        let mappable_mesh_buffers = mappable_mesh_buffers
+            && {
+                let supported = adapter
+                    .features()
+                    .contains(Features::MAPPABLE_PRIMARY_BUFFERS);
+                if supported {
+                    device_descriptor.features |= Features::MAPPABLE_PRIMARY_BUFFERS;
+                    if adapter.get_info().device_type == wgpu::DeviceType::DiscreteGpu {
+                        tracing::warn!("mappable mesh buffers are usually slower on discrete gpus");
+                    }
+                } else {
+                    tracing::warn!("adapter doesn't support mappable primary buffers. uploading egui meshes with a staging copy");
+                }
+                supported
+            };
+        debug!("requesting device with descriptor: {device_descriptor:#?}");
         let (device, queue) = adapter
-            .request_device(&device_descriptor, Default::default())
+            .request_device(&device_descriptor, trace_path.as_deref())
             .await
             .expect("failed to create wgpu device");
 
@@ -153,8 +739,24 @@ impl WgpuBackend {
             &surface_formats_priority,
             &mut surface_config,
         );
+        // reconfigure_surface skips the surface created above. so, we validate the alpha mode here
+        if let Some(surface) = surface.as_ref() {
+            Self::validate_alpha_mode(surface, &adapter, &mut surface_config);
+            Self::validate_present_mode(surface, &adapter, &mut surface_config);
+            Self::validate_surface_usage(&adapter, &mut surface_config);
+        }
 
-        let painter = EguiPainter::new(&device, surface_config.format);
+        let (device_lost, captured_errors) = Self::register_error_handler(&device);
+        let painter = Self::create_painter(
+            &device,
+            &adapter,
+            surface_config.format,
+            anisotropy_clamp,
+            custom_shader_src,
+            screen_size_push_constants,
+            depth_stencil_format,
+            mappable_mesh_buffers,
+        );
 
         Self {
             instance,
@@ -164,56 +766,1164 @@ impl WgpuBackend {
             painter,
             surface,
             surface_config,
-            surface_view: None,
-            surface_current_image: None,
-            command_encoders: Vec::new(),
-            surface_formats_priority,
+            surface_view: None,
+            surface_current_image: None,
+            command_encoders: Vec::new(),
+            surface_formats_priority,
+            clear_color: None,
+            captured_errors,
+            rendering_enabled: true,
+            poll_after_submit,
+            device_lost,
+            last_configured_size: [0, 0],
+            layers: Vec::new(),
+            queued_layers: Vec::new(),
+            depth_stencil_usage: depth_stencil_usage | TextureUsages::RENDER_ATTACHMENT,
+            depth_stencil: None,
+            on_surface_format_changed,
+        }
+    }
+    /// creates the backend using an existing wgpu instance, adapter, device and queue instead of creating our own.
+    /// useful to embed egui into a larger wgpu application (or share the device with another renderer).
+    /// `config` only has the options of `WgpuConfig` which don't depend on creating the device. they are used just like in
+    /// `Self::new_async`. options which need device features (eg: push constants) are only enabled if the device has them.
+    /// the surface (if there's a window) is created with the provided `instance`, so the adapter must be compatible with it.
+    ///
+    /// NOTE: this installs our own uncaptured error handler on `device` (to detect a lost device, see `Self::device_lost`),
+    /// which replaces any handler you set with `Device::on_uncaptured_error` before. set yours again afterwards if you need it,
+    /// but then `Self::poll_errors` no longer sees the device's errors and `Self::device_lost` only reports a lost surface.
+    pub fn from_existing<W: WindowBackend>(
+        window_backend: &mut W,
+        instance: Arc<Instance>,
+        adapter: Arc<Adapter>,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        config: ExistingDeviceConfig,
+    ) -> Self {
+        let ExistingDeviceConfig {
+            surface_formats_priority,
+            mut surface_config,
+            anisotropy_clamp,
+            custom_shader_src,
+            poll_after_submit,
+            screen_size_push_constants,
+            depth_stencil_format,
+            depth_stencil_usage,
+            mappable_mesh_buffers,
+            on_surface_format_changed,
+        } = config;
+        info!("using existing adapter: {:?}", adapter.get_info());
+        let mut surface = None;
+        Self::reconfigure_surface(
+            window_backend,
+            &mut surface,
+            &instance,
+            &adapter,
+            &device,
+            &surface_formats_priority,
+            &mut surface_config,
+        );
+        let (device_lost, captured_errors) = Self::register_error_handler(&device);
+        let painter = Self::create_painter(
+            &device,
+            &adapter,
+            surface_config.format,
+            anisotropy_clamp,
+            custom_shader_src,
+            screen_size_push_constants,
+            depth_stencil_format,
+            mappable_mesh_buffers,
+        );
+        Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            painter,
+            surface,
+            surface_config,
+            surface_view: None,
+            surface_current_image: None,
+            command_encoders: Vec::new(),
+            surface_formats_priority,
+            clear_color: None,
+            captured_errors,
+            rendering_enabled: true,
+            poll_after_submit,
+            device_lost,
+            last_configured_size: [0, 0],
+            layers: Vec::new(),
+            queued_layers: Vec::new(),
+            depth_stencil_usage: depth_stencil_usage | TextureUsages::RENDER_ATTACHMENT,
+            depth_stencil: None,
+            on_surface_format_changed,
+        }
+    }
+    /// creates the main painter with the painter related options of `WgpuConfig` / `ExistingDeviceConfig`.
+    /// invalid or unsupported options are replaced with their defaults (with a warning).
+    #[allow(clippy::too_many_arguments)]
+    fn create_painter(
+        device: &Device,
+        adapter: &Adapter,
+        format: TextureFormat,
+        anisotropy_clamp: Option<NonZeroU8>,
+        custom_shader_src: Option<String>,
+        screen_size_push_constants: bool,
+        depth_stencil_format: Option<TextureFormat>,
+        mappable_mesh_buffers: bool,
+    ) -> EguiPainter {
+        let anisotropy_clamp = anisotropy_clamp.filter(|clamp| {
+            if !matches!(clamp.get(), 1 | 2 | 4 | 8 | 16) {
+                tracing::warn!("invalid anisotropy clamp {clamp}. valid values are 1, 2, 4, 8 and 16. disabling anisotropic filtering");
+                return false;
+            }
+            if !adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING)
+            {
+                tracing::warn!("adapter doesn't support anisotropic filtering. disabling anisotropic filtering");
+                return false;
+            }
+            true
+        });
+        let mut painter = EguiPainter::new(device, format);
+        painter.set_linear_sampler_anisotropy(device, anisotropy_clamp);
+        if let Some(custom_shader_src) = custom_shader_src {
+            if let Err(e) = painter.set_custom_shader_src(device, Some(custom_shader_src)) {
+                tracing::warn!("invalid custom egui shader: {e}. using the default shader instead");
+            }
+        }
+        if screen_size_push_constants {
+            if let Err(e) = painter.set_screen_size_push_constants(device, true) {
+                tracing::warn!("failed to enable screen size push constants: {e}. using a uniform buffer instead");
+            }
+        }
+        if let Err(e) = painter.set_depth_stencil_format(device, depth_stencil_format) {
+            tracing::warn!(
+                "invalid depth stencil format: {e}. not using a depth stencil attachment"
+            );
+        }
+        if mappable_mesh_buffers {
+            if let Err(e) = painter.set_mappable_mesh_buffers(device, true) {
+                tracing::warn!("failed to enable mappable mesh buffers: {e}");
+            }
+        }
+        painter
+    }
+    /// This basically checks if the surface needs creating. and then if needed, creates surface if window exists.
+    /// then, it does all the work of configuring the surface.
+    /// this is used during resume events to create a surface.
+    fn reconfigure_surface<W: WindowBackend>(
+        window_backend: &mut W,
+        surface: &mut Option<Surface>,
+        instance: &Instance,
+        adapter: &Adapter,
+        device: &Device,
+        surface_formats_priority: &[TextureFormat],
+        surface_config: &mut SurfaceConfiguration,
+    ) {
+        if surface.is_some() {
+            return;
+        }
+        if let Some(window) = window_backend.get_window() {
+            *surface = Some(unsafe { instance.create_surface(window) });
+
+            let supported_formats = surface.as_ref().unwrap().get_supported_formats(adapter);
+            debug!("supported formats of the surface: {supported_formats:#?}");
+
+            let mut compatible_format_found = false;
+            for sfmt in surface_formats_priority.iter() {
+                debug!("checking if {sfmt:?} is supported");
+                if supported_formats.contains(sfmt) {
+                    debug!("{sfmt:?} is supported. setting it as surface format");
+                    surface_config.format = *sfmt;
+                    compatible_format_found = true;
+                    break;
+                }
+            }
+            if !compatible_format_found {
+                tracing::error!("could not find compatible surface format from user provided formats. using the first supported format instead");
+                surface_config.format = supported_formats
+                    .first()
+                    .copied()
+                    .expect("surface has zero supported texture formats");
+            }
+            Self::validate_alpha_mode(surface.as_ref().unwrap(), adapter, surface_config);
+            Self::validate_present_mode(surface.as_ref().unwrap(), adapter, surface_config);
+            Self::validate_surface_usage(adapter, surface_config);
+            let size = window_backend.get_live_physical_size_framebuffer().unwrap();
+            surface_config.width = size[0];
+            surface_config.height = size[1];
+
+            surface.as_ref().unwrap().configure(device, surface_config);
+        }
+    }
+    /// if the surface doesn't support `surface_config.alpha_mode`, we warn and fall back to the first supported alpha mode
+    fn validate_alpha_mode(
+        surface: &Surface,
+        adapter: &Adapter,
+        surface_config: &mut SurfaceConfiguration,
+    ) {
+        let supported_alpha_modes = surface.get_supported_alpha_modes(adapter);
+        debug!("supported alpha modes of the surface: {supported_alpha_modes:?}");
+        if surface_config.alpha_mode != wgpu::CompositeAlphaMode::Auto
+            && !supported_alpha_modes.contains(&surface_config.alpha_mode)
+        {
+            let fallback = supported_alpha_modes
+                .first()
+                .copied()
+                .unwrap_or(wgpu::CompositeAlphaMode::Auto);
+            tracing::warn!(
+                "alpha mode {:?} is not supported by the surface. using {fallback:?} instead",
+                surface_config.alpha_mode
+            );
+            surface_config.alpha_mode = fallback;
+        }
+    }
+    /// resolves `AutoVsync` / `AutoNoVsync` into the mode that wgpu would pick, so that `surface_config.present_mode` is always
+    /// the effective mode. if the surface doesn't support the present mode, we warn and fall back to `Fifo` (always supported).
+    fn validate_present_mode(
+        surface: &Surface,
+        adapter: &Adapter,
+        surface_config: &mut SurfaceConfiguration,
+    ) {
+        let supported_present_modes = surface.get_supported_present_modes(adapter);
+        debug!("supported present modes of the surface: {supported_present_modes:?}");
+        let candidates: &[PresentMode] = match surface_config.present_mode {
+            PresentMode::AutoVsync => &[PresentMode::FifoRelaxed, PresentMode::Fifo],
+            PresentMode::AutoNoVsync => &[
+                PresentMode::Immediate,
+                PresentMode::Mailbox,
+                PresentMode::Fifo,
+            ],
+            _ => std::slice::from_ref(&surface_config.present_mode),
+        };
+        let present_mode = candidates
+            .iter()
+            .copied()
+            .find(|mode| supported_present_modes.contains(mode))
+            .unwrap_or_else(|| {
+                tracing::warn!(
+                    "present mode {:?} is not supported by the surface. using Fifo instead",
+                    surface_config.present_mode
+                );
+                PresentMode::Fifo
+            });
+        surface_config.present_mode = present_mode;
+    }
+    /// the texture usages that surfaces of the adapter's backend support.
+    /// wgpu 0.14 can't query them, so this is based on what the backends of wgpu-hal report:
+    /// the gl backend only supports `RENDER_ATTACHMENT`. the others also support copying from / to the surface.
+    pub fn surface_usages(adapter: &Adapter) -> TextureUsages {
+        match adapter.get_info().backend {
+            Backend::Gl => TextureUsages::RENDER_ATTACHMENT,
+            _ => {
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::COPY_DST
+            }
+        }
+    }
+    /// if `surface_config.usage` contains usages not in `Self::surface_usages`, we warn and remove them.
+    /// otherwise, configuring the surface would panic.
+    fn validate_surface_usage(adapter: &Adapter, surface_config: &mut SurfaceConfiguration) {
+        let supported_usages = Self::surface_usages(adapter);
+        let unsupported_usages = surface_config.usage - supported_usages;
+        if !unsupported_usages.is_empty() {
+            tracing::warn!(
+                "surface usages {unsupported_usages:?} are not supported by the surface. removing them"
+            );
+            surface_config.usage &= supported_usages;
+        }
+    }
+    /// changes the present mode at runtime (eg: a vsync toggle in the settings). see `WgpuConfigBuilder::present_mode`.
+    /// the surface is reconfigured immediately, so don't call this between `GfxBackend::prepare_frame` and `GfxBackend::present`.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.surface_config.present_mode = present_mode;
+        if let Some(surface) = self.surface.as_ref() {
+            Self::validate_present_mode(surface, &self.adapter, &mut self.surface_config);
+            if self.surface_config.width != 0 && self.surface_config.height != 0 {
+                surface.configure(&self.device, &self.surface_config);
+            }
+        }
+    }
+    /// rough number of frames that can be queued between submitting a frame and it being shown on screen,
+    /// based on the effective present mode (wgpu 0.14 doesn't report the real swapchain length).
+    /// `Fifo` / `FifoRelaxed` queue up to ~3 frames, `Mailbox` replaces the single queued frame and `Immediate` doesn't queue at all.
+    /// useful to tune latency sensitive apps (eg: overlays) along with `Self::set_present_mode`.
+    pub fn approximate_frame_latency(&self) -> u32 {
+        match self.surface_config.present_mode {
+            PresentMode::Immediate => 0,
+            PresentMode::Mailbox => 1,
+            PresentMode::Fifo
+            | PresentMode::FifoRelaxed
+            | PresentMode::AutoVsync
+            | PresentMode::AutoNoVsync => 3,
+        }
+    }
+    /// see `EguiPainter::register_native_texture`
+    pub fn register_native_texture(
+        &mut self,
+        view: &TextureView,
+        filter: egui::TextureFilter,
+        linear_view: bool,
+    ) -> TextureId {
+        self.painter
+            .register_native_texture(&self.device, view, filter, linear_view)
+    }
+    /// creates a texture from tightly packed (srgb encoded) RGBA8 pixels, uploads them and registers the texture as a user texture.
+    /// useful to show images (eg: decoded PNGs) without dealing with wgpu textures yourself.
+    /// the texture lives as long as its bindgroup. free it with `Self::free_user_texture` once you don't need it.
+    /// for images which change or should be freed automatically, use `Self::register_image` instead.
+    ///
+    /// egui expects premultiplied alpha. most image decoders (eg: png) produce straight alpha, which shows dark edges
+    /// around transparent areas. use `Self::load_image_rgba_unmultiplied` for those.
+    pub fn load_image_rgba(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        filter: egui::TextureFilter,
+    ) -> TextureId {
+        let texture = self.create_image_texture(rgba, width, height);
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        // the format is srgb, so the gpu decodes the colors for us
+        self.register_native_texture(&view, filter, false)
+    }
+    /// like `Self::load_image_rgba`, but for straight (non-premultiplied) alpha. see `premultiply_rgba`
+    pub fn load_image_rgba_unmultiplied(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        filter: egui::TextureFilter,
+    ) -> TextureId {
+        self.load_image_rgba(&premultiply_rgba(rgba), width, height, filter)
+    }
+    /// like `Self::register_image`, but for straight (non-premultiplied) alpha. see `premultiply_rgba`
+    pub fn register_image_unmultiplied(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        filter: egui::TextureFilter,
+    ) -> ImageHandle {
+        self.register_image(&premultiply_rgba(rgba), width, height, filter)
+    }
+    /// like `Self::load_image_rgba`, but returns an `ImageHandle` which frees the texture when dropped and can be updated
+    /// with new pixels (eg: video frames or plots rendered on the cpu) while keeping its `TextureId`.
+    pub fn register_image(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        filter: egui::TextureFilter,
+    ) -> ImageHandle {
+        let texture = self.create_image_texture(rgba, width, height);
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let id = self.register_native_texture(&view, filter, false);
+        ImageHandle {
+            id,
+            texture,
+            size: [width, height],
+            filter,
+            dropped_user_textures: self.painter.dropped_user_textures.clone(),
+        }
+    }
+    /// creates an `Rgba8UnormSrgb` texture of the given size and uploads the tightly packed rgba pixels into it
+    fn create_image_texture(&self, rgba: &[u8], width: u32, height: u32) -> Texture {
+        assert_eq!(
+            rgba.len(),
+            width as usize * height as usize * 4,
+            "rgba pixels length doesn't match the image size {width}x{height}"
+        );
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: Some("egui user image"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        write_image_texture(&self.queue, &texture, rgba, width, height);
+        texture
+    }
+    /// see `EguiPainter::register_native_texture_view`
+    pub fn register_native_texture_view(
+        &mut self,
+        view: &TextureView,
+        sampler_descriptor: &SamplerDescriptor,
+        linear_view: bool,
+    ) -> TextureId {
+        self.painter.register_native_texture_view(
+            &self.device,
+            view,
+            sampler_descriptor,
+            linear_view,
+        )
+    }
+    /// see `EguiPainter::register_native_texture_view_with_id`
+    pub fn register_native_texture_view_with_id(
+        &mut self,
+        user_id: u64,
+        view: &TextureView,
+        sampler_descriptor: &SamplerDescriptor,
+        linear_view: bool,
+    ) -> TextureId {
+        self.painter.register_native_texture_view_with_id(
+            &self.device,
+            user_id,
+            view,
+            sampler_descriptor,
+            linear_view,
+        )
+    }
+    /// see `EguiPainter::free_user_texture`
+    pub fn free_user_texture(&mut self, texture_id: TextureId) {
+        self.painter.free_user_texture(texture_id);
+    }
+    /// see `EguiPainter::register_native_texture_with_id`
+    pub fn register_native_texture_with_id(
+        &mut self,
+        user_id: u64,
+        view: &TextureView,
+        filter: egui::TextureFilter,
+        linear_view: bool,
+    ) -> TextureId {
+        self.painter.register_native_texture_with_id(
+            &self.device,
+            user_id,
+            view,
+            filter,
+            linear_view,
+        )
+    }
+    /// reserve capacity in egui's vertex and index buffers up front. see `EguiPainter::preallocate`
+    pub fn preallocate_egui_buffers(&mut self, vertices: usize, indices: usize) {
+        self.painter.preallocate(&self.device, vertices, indices);
+    }
+    /// adds a layer for an additional `egui::Context` (eg: a debug overlay on top of the main ui) and returns its index.
+    /// each layer has its own painter, as the managed texture ids of different contexts would collide.
+    /// use `Self::queue_layer` every frame to draw it.
+    pub fn add_layer(&mut self) -> usize {
+        let mut painter = EguiPainter::new(&self.device, self.surface_config.format);
+        painter.set_linear_sampler_anisotropy(&self.device, self.painter.linear_sampler_anisotropy);
+        painter
+            .set_custom_shader_src(&self.device, self.painter.custom_shader_src.clone())
+            .expect("main painter's custom shader was already validated");
+        painter
+            .set_screen_size_push_constants(&self.device, self.painter.screen_size_push_constants)
+            .expect("main painter's push constants were already validated");
+        // layers are drawn in the same render pass. so, their pipelines must match its depth-stencil attachment
+        painter
+            .set_depth_stencil_format(&self.device, self.painter.depth_stencil_format)
+            .expect("main painter's depth stencil format was already validated");
+        painter
+            .set_mappable_mesh_buffers(&self.device, self.painter.mappable_mesh_buffers)
+            .expect("main painter's mappable mesh buffers were already validated");
+        self.layers.push(painter);
+        self.layers.len() - 1
+    }
+    /// queues the output of another egui context to be drawn into the surface by `GfxBackend::render`, after the main context.
+    /// layers are drawn in the order they were queued, so later layers are on top.
+    /// create the `EguiGfxData` yourself in `UserAppData::run`: run the other context with (a clone of) the raw input,
+    /// then tessellate its shapes and use the main context's screen size. queue each layer at most once per frame.
+    ///
+    /// NOTE: even when the layer has nothing to draw, keep queuing its output, as it contains texture updates of that context.
+    pub fn queue_layer(&mut self, layer: usize, egui_gfx_data: EguiGfxData) {
+        assert!(
+            layer < self.layers.len(),
+            "invalid egui layer index {layer}"
+        );
+        self.queued_layers.push((layer, egui_gfx_data));
+    }
+    /// the painter which holds egui's wgpu resources (pipelines, buffers, textures).
+    pub fn painter(&self) -> &EguiPainter {
+        &self.painter
+    }
+    /// stats of the main egui context's last frame. see `EguiPainter::last_frame_stats`
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.painter.last_frame_stats()
+    }
+    /// for advanced users who want to draw egui into their own render pass (own encoder, own color attachment etc..)
+    /// instead of using `GfxBackend::render`. in your own frame loop:
+    /// 1. `EguiPainter::upload_egui_data` with your command encoder, the `EguiGfxData` of the frame and the physical size of your attachment.
+    /// 2. `EguiPainter::draw_egui_with_renderpass` inside your render pass. the attachment must have the surface format.
+    /// 3. submit the encoder before the next `EguiPainter::upload_egui_data`.
+    ///
+    /// don't call `GfxBackend::render` for the same frame, as it would upload the same egui data again.
+    /// `Self::prepare_egui` and `Self::paint_egui` wrap this flow and also handle a format other than the surface format.
+    pub fn painter_mut(&mut self) -> &mut EguiPainter {
+        &mut self.painter
+    }
+    /// the texture of the current swapchain image (eg: for video encoders or external compositors).
+    /// only `Some` between `GfxBackend::prepare_frame` and `GfxBackend::present`, as the surface texture is acquired in
+    /// `prepare_frame` and presented in `present`. the borrow can't outlive the frame anyway, but don't keep any clones
+    /// of its views or copies of it around either. copying from it requires `TextureUsages::COPY_SRC` (see `WgpuConfigBuilder::surface_usage`).
+    pub fn current_surface_texture(&self) -> Option<&Texture> {
+        self.surface_current_image
+            .as_ref()
+            .map(|surface_texture| &surface_texture.texture)
+    }
+    /// format of the surface chosen from `WgpuConfig::surface_formats_priority`. pipelines used in paint callbacks must
+    /// use this format for their color target. it can change when the surface is recreated on resume.
+    /// see `WgpuConfig::on_surface_format_changed`
+    pub fn surface_format(&self) -> TextureFormat {
+        self.surface_config.format
+    }
+    /// see `WgpuConfig::on_surface_format_changed`
+    pub fn set_on_surface_format_changed(
+        &mut self,
+        on_surface_format_changed: Option<Box<dyn FnMut(&Device, TextureFormat)>>,
+    ) {
+        self.on_surface_format_changed = on_surface_format_changed;
+    }
+    /// format of the egui render pass's depth-stencil attachment. pipelines used in paint callbacks must use this format
+    /// in their `DepthStencilState` (or no depth-stencil state at all, if this is `None`). see `WgpuConfig::depth_stencil_format`
+    pub fn depth_stencil_format(&self) -> Option<TextureFormat> {
+        self.painter.depth_stencil_format()
+    }
+    /// the depth-stencil texture used by the last egui render pass. `None` if there's no depth-stencil format or nothing was drawn yet.
+    pub fn depth_stencil_texture(&self) -> Option<&Texture> {
+        self.depth_stencil
+            .as_ref()
+            .map(|depth_stencil| &depth_stencil.texture)
+    }
+    /// (re)creates `Self::depth_stencil` if the size or the painter's depth-stencil format changed and returns its view
+    fn depth_stencil_view(&mut self, size: [u32; 2]) -> Option<&TextureView> {
+        let format = self.painter.depth_stencil_format()?;
+        if !matches!(&self.depth_stencil, Some(depth_stencil) if depth_stencil.size == size && depth_stencil.format == format)
+        {
+            let texture = self.device.create_texture(&TextureDescriptor {
+                label: Some("egui depth stencil texture"),
+                size: Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: self.depth_stencil_usage,
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            self.depth_stencil = Some(DepthStencilTarget {
+                texture,
+                view,
+                size,
+                format,
+            });
+        }
+        self.depth_stencil
+            .as_ref()
+            .map(|depth_stencil| &depth_stencil.view)
+    }
+    /// first half of drawing egui into a render pass that you own (eg: when egui is one pass in another engine's frame graph).
+    /// uploads the textures and meshes of `egui_gfx_data` and frees the textures that egui (or `Self::free_user_texture`)
+    /// freed in the previous frame. `size` is the physical size of the attachment and `format` its format.
+    /// texture uploads are recorded as copies into `encoder`. so, begin your pass on the same encoder (or a later one).
+    /// the pipelines are created once for each `format` and reused in later frames.
+    /// then, call `Self::paint_egui` inside your render pass. the frame loop looks like:
+    /// 1. `prepare_egui` with this frame's `EguiGfxData`.
+    /// 2. record your pass and call `paint_egui` in it.
+    /// 3. submit `encoder` (and your other command buffers) before the next `prepare_egui`.
+    ///
+    /// textures freed by egui are kept alive until the next `prepare_egui`, so the draws recorded in step 2 stay valid.
+    /// this uses the main painter, so don't call `GfxBackend::render` or `Self::render_to_target` for the same `EguiGfxData`.
+    pub fn prepare_egui(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        egui_gfx_data: EguiGfxData,
+        size: [u32; 2],
+        format: TextureFormat,
+    ) {
+        if self.device_lost() {
+            return;
+        }
+        self.painter.on_resume(&self.device, format);
+        self.painter
+            .upload_egui_data(&self.device, &self.queue, encoder, egui_gfx_data, size);
+    }
+    /// second half of `Self::prepare_egui`. records egui's draw calls (and paint callbacks) into your render pass.
+    /// the color attachment must have the format passed to `Self::prepare_egui`. if `Self::depth_stencil_format` is `Some`,
+    /// the pass must have a depth-stencil attachment of that format. otherwise, it must not have one.
+    pub fn paint_egui<'rpass>(&'rpass self, rpass: &mut RenderPass<'rpass>) {
+        if self.device_lost() {
+            return;
+        }
+        self.painter.draw_egui_with_renderpass(rpass);
+    }
+    /// draws egui into `view` (eg: a texture shown in a tab of a 3d app or a vr eye buffer) instead of the window surface
+    /// and submits immediately (blocking until the gpu is done). `size` is the physical size of the view and `format` must be the format of the view.
+    /// the render pipelines are created once for each `format` and reused in later frames. so, alternating between
+    /// the window surface and a target of a different format doesn't rebuild them.
+    ///
+    /// this uses the main painter, so don't call `GfxBackend::render` for the same `EguiGfxData`.
+    /// the clear color (see `Self::set_clear_color`) applies to the target too.
+    pub fn render_to_target(
+        &mut self,
+        egui_gfx_data: EguiGfxData,
+        view: &TextureView,
+        size: [u32; 2],
+        format: TextureFormat,
+    ) {
+        if self.device_lost() {
+            return;
+        }
+        self.painter.on_resume(&self.device, format);
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("egui render target command encoder"),
+            });
+        self.painter.upload_egui_data(
+            &self.device,
+            &self.queue,
+            &mut command_encoder,
+            egui_gfx_data,
+            size,
+        );
+        let depth_stencil_format = self.painter.depth_stencil_format();
+        // the painter's pipelines expect a depth-stencil attachment. so, we need one of the target's size too
+        self.depth_stencil_view(size);
+        {
+            let mut egui_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("egui render target pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: match self.clear_color {
+                            Some(color) => LoadOp::Clear(color),
+                            None => LoadOp::Load,
+                        },
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: self
+                    .depth_stencil
+                    .as_ref()
+                    .zip(depth_stencil_format)
+                    .map(|(depth_stencil, format)| {
+                        depth_stencil_attachment(&depth_stencil.view, format)
+                    }),
+            });
+            self.painter.draw_egui_with_renderpass(&mut egui_pass);
+        }
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        // there's no surface present to drive the queue. so, wait here, which also fires the pending map callbacks
+        self.device.poll(Maintain::Wait);
+    }
+    /// benchmarks egui's throughput without a window or vsync. runs `count` frames of a fresh `egui::Context` as fast as possible,
+    /// building the ui with `build_ui` every frame (just like `UserAppData::run`), and returns the timings of each frame.
+    /// frames are drawn into an offscreen texture with the surface's size and format (1280x720 if there's no surface yet)
+    /// and `pixels_per_point` of 1.0.
+    ///
+    /// the context gets its own painter, so this doesn't touch the textures of your main context. every frame waits for the gpu.
+    /// gpu times are only measured if the device was created with `Features::TIMESTAMP_QUERY`. not supported on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_frames_headless(
+        &mut self,
+        count: usize,
+        mut build_ui: impl FnMut(&egui::Context),
+    ) -> Vec<HeadlessFrameTiming> {
+        if self.device_lost() {
+            return Vec::new();
+        }
+        let size = if self.surface_config.width == 0 || self.surface_config.height == 0 {
+            [1280, 720]
+        } else {
+            [self.surface_config.width, self.surface_config.height]
+        };
+        let format = self.surface_config.format;
+        let target = self.device.create_texture(&TextureDescriptor {
+            label: Some("headless benchmark target"),
+            size: Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+        });
+        let view = target.create_view(&TextureViewDescriptor::default());
+        let mut painter = EguiPainter::new(&self.device, format);
+        // a start and end timestamp per frame, resolved straight into a mappable buffer
+        let timestamps = self
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let query_set = self.device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("headless benchmark timestamps"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: 2,
+                });
+                let buffer = self.device.create_buffer(&BufferDescriptor {
+                    label: Some("headless benchmark timestamps buffer"),
+                    size: 2 * std::mem::size_of::<u64>() as u64,
+                    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                (query_set, buffer)
+            });
+        // nanoseconds per timestamp tick
+        let timestamp_period = self.queue.get_timestamp_period() as f64;
+        let egui_context = egui::Context::default();
+        let screen_size_logical = [size[0] as f32, size[1] as f32];
+        let mut timings = Vec::with_capacity(count);
+        for _ in 0..count {
+            let frame_start = std::time::Instant::now();
+            let raw_input = egui::RawInput {
+                screen_rect: Some(Rect::from_min_size(
+                    Default::default(),
+                    screen_size_logical.into(),
+                )),
+                pixels_per_point: Some(1.0),
+                ..Default::default()
+            };
+            let output = egui_context.run(raw_input, |ctx| build_ui(ctx));
+            let meshes = egui_context.tessellate(output.shapes);
+            let egui_cpu_time = frame_start.elapsed();
+
+            let render_start = std::time::Instant::now();
+            let mut command_encoder =
+                self.device
+                    .create_command_encoder(&CommandEncoderDescriptor {
+                        label: Some("headless benchmark command encoder"),
+                    });
+            painter.upload_egui_data(
+                &self.device,
+                &self.queue,
+                &mut command_encoder,
+                EguiGfxData {
+                    meshes,
+                    textures_delta: output.textures_delta,
+                    screen_size_logical,
+                    viewport: None,
+                },
+                size,
+            );
+            if let Some((query_set, _)) = timestamps.as_ref() {
+                command_encoder.write_timestamp(query_set, 0);
+            }
+            {
+                let mut egui_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("headless benchmark render pass"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                painter.draw_egui_with_renderpass(&mut egui_pass);
+            }
+            if let Some((query_set, buffer)) = timestamps.as_ref() {
+                command_encoder.write_timestamp(query_set, 1);
+                command_encoder.resolve_query_set(query_set, 0..2, buffer, 0);
+            }
+            self.queue.submit(std::iter::once(command_encoder.finish()));
+            let render_cpu_time = render_start.elapsed();
+
+            let gpu_wait_start = std::time::Instant::now();
+            self.device.poll(Maintain::Wait);
+            let gpu_wait_time = gpu_wait_start.elapsed();
+
+            let gpu_time = timestamps.as_ref().map(|(_, buffer)| {
+                let slice = buffer.slice(..);
+                slice.map_async(wgpu::MapMode::Read, |result| {
+                    if let Err(e) = result {
+                        tracing::error!("failed to map timestamps buffer: {e}");
+                    }
+                });
+                self.device.poll(Maintain::Wait);
+                let ticks = {
+                    let mapped = slice.get_mapped_range();
+                    let timestamps: &[u64] = cast_slice(&mapped[..]);
+                    timestamps[1].saturating_sub(timestamps[0])
+                };
+                buffer.unmap();
+                std::time::Duration::from_nanos((ticks as f64 * timestamp_period) as u64)
+            });
+            timings.push(HeadlessFrameTiming {
+                egui_cpu_time,
+                render_cpu_time,
+                gpu_wait_time,
+                gpu_time,
+            });
+        }
+        timings
+    }
+    /// creates a surface for an additional window which shares our instance / adapter / device / queue.
+    /// `size` is the framebuffer size of the window in physical pixels.
+    ///
+    /// egui textures are not shared between contexts, so the returned surface has its own `EguiPainter`.
+    /// use a separate `egui::Context` for the second window, route its window's events into that context's `RawInput`,
+    /// and render its output with `SecondarySurface::render_frame`.
+    pub fn create_secondary_surface(
+        &self,
+        window: &(impl HasRawWindowHandle + HasRawDisplayHandle),
+        size: [u32; 2],
+    ) -> SecondarySurface {
+        let surface = unsafe { self.instance.create_surface(window) };
+        let supported_formats = surface.get_supported_formats(&self.adapter);
+        let mut surface_config = self.surface_config.clone();
+        // prefer the main surface format, so that users can reuse pipelines for both windows
+        if !supported_formats.contains(&surface_config.format) {
+            surface_config.format = supported_formats
+                .first()
+                .copied()
+                .expect("surface has zero supported texture formats");
+        }
+        surface_config.width = size[0];
+        surface_config.height = size[1];
+        // wgpu panics if we configure the surface with zero size (eg: minimized window)
+        let minimized = size[0] == 0 || size[1] == 0;
+        if !minimized {
+            surface.configure(&self.device, &surface_config);
+        }
+        let mut painter = EguiPainter::new(&self.device, surface_config.format);
+        painter.set_linear_sampler_anisotropy(&self.device, self.painter.linear_sampler_anisotropy);
+        painter
+            .set_custom_shader_src(&self.device, self.painter.custom_shader_src.clone())
+            .expect("main painter's custom shader was already validated");
+        painter
+            .set_screen_size_push_constants(&self.device, self.painter.screen_size_push_constants)
+            .expect("main painter's push constants were already validated");
+        painter
+            .set_mappable_mesh_buffers(&self.device, self.painter.mappable_mesh_buffers)
+            .expect("main painter's mappable mesh buffers were already validated");
+        SecondarySurface {
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+            surface,
+            surface_config,
+            painter,
+            clear_color: self.clear_color,
+            minimized,
         }
     }
-    /// This basically checks if the surface needs creating. and then if needed, creates surface if window exists.
-    /// then, it does all the work of configuring the surface.
-    /// this is used during resume events to create a surface.
-    fn reconfigure_surface<W: WindowBackend>(
-        window_backend: &mut W,
-        surface: &mut Option<Surface>,
-        instance: &Instance,
-        adapter: &Adapter,
-        device: &Device,
-        surface_formats_priority: &[TextureFormat],
-        surface_config: &mut SurfaceConfiguration,
-    ) {
-        if surface.is_some() {
-            return;
-        }
-        if let Some(window) = window_backend.get_window() {
-            *surface = Some(unsafe { instance.create_surface(window) });
+}
 
-            let supported_formats = surface.as_ref().unwrap().get_supported_formats(adapter);
-            debug!("supported formats of the surface: {supported_formats:#?}");
+/// rows of a buffer used in texture copies must be aligned to `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` (256 bytes).
+/// returns the aligned bytes per row for a RGBA8 row of `width` pixels
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded_bytes_per_row = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded_bytes_per_row + (align - unpadded_bytes_per_row % align) % align
+}
+#[cfg(not(target_arch = "wasm32"))]
+fn is_bgra(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    )
+}
+/// maps the buffer, waits for it and copies the rows into `pixels` without the padding. see `unpad_mapped_buffer`.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_padded_buffer(
+    device: &Device,
+    buffer: &Buffer,
+    unpadded_bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+    bgra_to_rgba: bool,
+    pixels: &mut Vec<u8>,
+) {
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |result| {
+        if let Err(e) = result {
+            tracing::error!("failed to map readback buffer: {e}");
+        }
+    });
+    device.poll(wgpu::Maintain::Wait);
+    unpad_mapped_buffer(
+        buffer,
+        unpadded_bytes_per_row,
+        padded_bytes_per_row,
+        bgra_to_rgba,
+        pixels,
+    );
+}
+/// copies the rows of an already mapped buffer into `pixels` without the padding and unmaps the buffer.
+/// `pixels` is cleared first, so that it can be reused. if `bgra_to_rgba` is true, we swap the red and blue channels.
+#[cfg(not(target_arch = "wasm32"))]
+fn unpad_mapped_buffer(
+    buffer: &Buffer,
+    unpadded_bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+    bgra_to_rgba: bool,
+    pixels: &mut Vec<u8>,
+) {
+    pixels.clear();
+    {
+        let mapped = buffer.slice(..).get_mapped_range();
+        for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+    if bgra_to_rgba {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+}
+/// one of the two staging buffers of a `FrameReader`.
+#[cfg(not(target_arch = "wasm32"))]
+struct FrameReaderStagingBuffer {
+    buffer: Buffer,
+    /// set by the `map_async` callback once the copy is finished and the buffer is mapped (or mapping failed).
+    map_result: Arc<std::sync::Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+    /// true from `FrameReader::request_frame` until the frame is consumed by `FrameReader::try_read`.
+    in_flight: bool,
+    /// whether the surface was bgra when the frame was requested
+    is_bgra: bool,
+}
+/// reads back the whole surface every frame without stalling the render thread. created by `WgpuBackend::frame_reader`.
+///
+/// call `Self::request_frame` after rendering to copy the surface into a staging buffer and start mapping it. call `Self::try_read`
+/// every frame (or from a later point in the same frame) to get the oldest requested frame once the gpu finished the copy.
+/// there are two staging buffers, so that one frame can be in flight while the previous one is read. frames are returned in the order
+/// they were requested and the readback usually lags a frame or two behind.
+/// the buffers and the pixels vec are reused across frames, so there's no per frame allocation (unless the surface is resized).
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FrameReader {
+    buffers: [FrameReaderStagingBuffer; 2],
+    /// index of the buffer that the next `Self::request_frame` copies into
+    write_index: usize,
+    /// index of the buffer that the next `Self::try_read` reads from
+    read_index: usize,
+    size: [u32; 2],
+    padded_bytes_per_row: u32,
+    pixels: Vec<u8>,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl FrameReader {
+    fn new(device: &Device, size: [u32; 2]) -> Self {
+        let padded_bytes_per_row = padded_bytes_per_row(size[0]);
+        let create_staging_buffer = || FrameReaderStagingBuffer {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("frame reader staging buffer"),
+                size: (padded_bytes_per_row * size[1]) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            map_result: Default::default(),
+            in_flight: false,
+            is_bgra: false,
+        };
+        Self {
+            buffers: [create_staging_buffer(), create_staging_buffer()],
+            write_index: 0,
+            read_index: 0,
+            size,
+            padded_bytes_per_row,
+            pixels: Vec::new(),
+        }
+    }
+    /// size of the frames in physical pixels
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+    /// copies the current surface texture into a free staging buffer and starts mapping it. doesn't wait for the gpu.
+    /// returns false (and skips this frame) if both staging buffers are still in flight, because `Self::try_read` didn't catch up yet,
+    /// or if the surface can't be read (see `WgpuBackend::read_rect`).
+    /// the staging buffers are recreated if the surface size changed, which drops any frames still in flight.
+    /// same requirements as `WgpuBackend::read_rect`.
+    pub fn request_frame(&mut self, backend: &mut WgpuBackend) -> bool {
+        let size = [backend.surface_config.width, backend.surface_config.height];
+        if size != self.size {
+            debug!("surface resized to {size:?}. recreating frame reader staging buffers");
+            *self = Self::new(&backend.device, size);
+        }
+        let padded_bytes_per_row = self.padded_bytes_per_row;
+        let staging = &mut self.buffers[self.write_index];
+        if staging.in_flight {
+            return false;
+        }
+        if !backend.copy_surface_to_buffer(
+            &staging.buffer,
+            [0, 0, size[0], size[1]],
+            padded_bytes_per_row,
+        ) {
+            return false;
+        }
+        let map_result = staging.map_result.clone();
+        staging
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *map_result
+                    .lock()
+                    .expect("failed to lock frame reader map result") = Some(result);
+            });
+        staging.in_flight = true;
+        staging.is_bgra = is_bgra(backend.surface_config.format);
+        self.write_index = (self.write_index + 1) % self.buffers.len();
+        true
+    }
+    /// returns the oldest requested frame if the gpu finished copying it. otherwise, returns `None` without blocking.
+    /// the pixels are tightly packed rows (`width * 4` bytes each, no padding) in the surface's format.
+    /// if `bgra_to_rgba` is true and the surface was bgra, we swap channels to return RGBA8.
+    /// this polls the device (without waiting), so that the map callbacks can fire even if nothing else is submitted.
+    pub fn try_read(&mut self, backend: &WgpuBackend, bgra_to_rgba: bool) -> Option<&[u8]> {
+        backend.device.poll(wgpu::Maintain::Poll);
+        let staging = &mut self.buffers[self.read_index];
+        if !staging.in_flight {
+            return None;
+        }
+        let map_result = staging
+            .map_result
+            .lock()
+            .expect("failed to lock frame reader map result")
+            .take()?;
+        staging.in_flight = false;
+        self.read_index = (self.read_index + 1) % self.buffers.len();
+        if let Err(e) = map_result {
+            tracing::error!("failed to map frame reader staging buffer: {e}");
+            return None;
+        }
+        unpad_mapped_buffer(
+            &staging.buffer,
+            self.size[0] * 4,
+            self.padded_bytes_per_row,
+            bgra_to_rgba && staging.is_bgra,
+            &mut self.pixels,
+        );
+        Some(&self.pixels)
+    }
+}
+/// timings of a frame rendered by `WgpuBackend::run_frames_headless`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessFrameTiming {
+    /// running the ui function and tessellating its output
+    pub egui_cpu_time: std::time::Duration,
+    /// uploading egui's data, recording the draw calls and submitting them
+    pub render_cpu_time: std::time::Duration,
+    /// time the cpu spent waiting for the gpu after submitting
+    pub gpu_wait_time: std::time::Duration,
+    /// time the gpu spent on the frame, measured with timestamp queries. `None` if `Features::TIMESTAMP_QUERY` is not enabled
+    pub gpu_time: Option<std::time::Duration>,
+}
+/// what the window surface supports with the chosen adapter. see `WgpuBackend::surface_capabilities`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceCapabilities {
+    /// supported texture formats. the first one is the preferred format
+    pub formats: Vec<TextureFormat>,
+    /// supported present modes. `PresentMode::Fifo` is always supported
+    pub present_modes: Vec<PresentMode>,
+    /// supported composite alpha modes
+    pub alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+}
 
-            let mut compatible_format_found = false;
-            for sfmt in surface_formats_priority.iter() {
-                debug!("checking if {sfmt:?} is supported");
-                if supported_formats.contains(sfmt) {
-                    debug!("{sfmt:?} is supported. setting it as surface format");
-                    surface_config.format = *sfmt;
-                    compatible_format_found = true;
-                    break;
-                }
-            }
-            if !compatible_format_found {
-                tracing::error!("could not find compatible surface format from user provided formats. using the first supported format instead");
-                surface_config.format = supported_formats
-                    .first()
-                    .copied()
-                    .expect("surface has zero supported texture formats");
-            }
-            let size = window_backend.get_live_physical_size_framebuffer().unwrap();
-            surface_config.width = size[0];
-            surface_config.height = size[1];
+/// A window surface created by `WgpuBackend::create_secondary_surface`.
+/// It shares device and queue with the `WgpuBackend`, but has its own `EguiPainter` as it will be drawing
+/// the output of a different egui context.
+pub struct SecondarySurface {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    surface: Surface,
+    pub surface_config: SurfaceConfiguration,
+    painter: EguiPainter,
+    /// copied from the `WgpuBackend` that created this. set with `Self::set_clear_color`
+    clear_color: Option<wgpu::Color>,
+    /// true while the framebuffer size is zero (eg: minimized window). we don't configure or draw the surface then.
+    minimized: bool,
+}
 
-            surface.as_ref().unwrap().configure(device, surface_config);
+impl SecondarySurface {
+    /// reconfigure the surface with the new framebuffer size in physical pixels.
+    /// a zero sized framebuffer (eg: minimized window) skips the configuration and `Self::render_frame` draws nothing until
+    /// the next non-zero resize.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        // wgpu panics if we configure the surface with zero size
+        self.minimized = size[0] == 0 || size[1] == 0;
+        if self.minimized {
+            debug!(
+                "skipping secondary surface configuration due to zero sized framebuffer: {size:?}"
+            );
+            return;
+        }
+        self.surface_config.width = size[0];
+        self.surface_config.height = size[1];
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+    /// the color that the surface is cleared with before drawing egui. `None` clears with transparent black,
+    /// as the surface only shows egui. see `WgpuBackend::set_clear_color`
+    pub fn set_clear_color(&mut self, clear_color: Option<wgpu::Color>) {
+        self.clear_color = clear_color;
+    }
+    /// acquires the swapchain image, draws egui, submits and presents. all in one go.
+    pub fn render_frame(&mut self, egui_gfx_data: EguiGfxData) {
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("secondary egui command encoder"),
+            });
+        if self.minimized {
+            // still upload, so that textures stay in sync with egui
+            self.painter.upload_egui_data(
+                &self.device,
+                &self.queue,
+                &mut command_encoder,
+                egui_gfx_data,
+                [self.surface_config.width, self.surface_config.height],
+            );
+            self.queue.submit(std::iter::once(command_encoder.finish()));
+            return;
+        }
+        let surface_image = self.surface.get_current_texture().unwrap_or_else(|e| {
+            self.surface.configure(&self.device, &self.surface_config);
+            self.surface.get_current_texture().expect(&format!(
+                "failed to get surface even after reconfiguration. {e}"
+            ))
+        });
+        let surface_view = surface_image.texture.create_view(&TextureViewDescriptor {
+            label: Some("secondary surface view"),
+            format: Some(self.surface_config.format),
+            dimension: Some(TextureViewDimension::D2),
+            aspect: TextureAspect::All,
+            base_mip_level: 0,
+            mip_level_count: None,
+            base_array_layer: 0,
+            array_layer_count: None,
+        });
+        self.painter.upload_egui_data(
+            &self.device,
+            &self.queue,
+            &mut command_encoder,
+            egui_gfx_data,
+            [self.surface_config.width, self.surface_config.height],
+        );
+        {
+            let mut egui_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("secondary egui render pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &surface_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(self.clear_color.unwrap_or(wgpu::Color::TRANSPARENT)),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.painter.draw_egui_with_renderpass(&mut egui_pass);
         }
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        surface_image.present();
     }
 }
 impl<W: WindowBackend> GfxBackend<W> for WgpuBackend {
@@ -225,11 +1935,13 @@ impl<W: WindowBackend> GfxBackend<W> for WgpuBackend {
 
     fn suspend(&mut self, _window_backend: &mut W) {
         self.surface = None;
+        self.last_configured_size = [0, 0];
         self.surface_current_image = None;
         self.surface_view = None;
     }
 
     fn resume(&mut self, window_backend: &mut W) {
+        let previous_format = self.surface_config.format;
         Self::reconfigure_surface(
             window_backend,
             &mut self.surface,
@@ -239,32 +1951,72 @@ impl<W: WindowBackend> GfxBackend<W> for WgpuBackend {
             &self.surface_formats_priority,
             &mut self.surface_config,
         );
+        if self.surface.is_some() {
+            self.last_configured_size = [self.surface_config.width, self.surface_config.height];
+        }
         self.painter
             .on_resume(&self.device, self.surface_config.format);
+        for layer in self.layers.iter_mut() {
+            layer.on_resume(&self.device, self.surface_config.format);
+        }
+        if self.surface_config.format != previous_format {
+            info!(
+                "surface format changed from {previous_format:?} to {:?}",
+                self.surface_config.format
+            );
+            if let Some(on_surface_format_changed) = self.on_surface_format_changed.as_mut() {
+                on_surface_format_changed(&self.device, self.surface_config.format);
+            }
+        }
     }
 
     fn prepare_frame(&mut self, framebuffer_size_update: bool, window_backend: &mut W) {
-        if framebuffer_size_update {
+        // without a surface, there's nothing to configure
+        if framebuffer_size_update && !self.device_lost() && self.surface.is_some() {
             let size = window_backend.get_live_physical_size_framebuffer().unwrap();
-            self.surface_config.width = size[0];
-            self.surface_config.height = size[1];
-            self.surface
-                .as_ref()
-                .unwrap()
-                .configure(&self.device, &self.surface_config);
+            // wgpu panics if we configure the surface with zero size (eg: minimized window). so, we keep the previous size
+            if size[0] == 0 || size[1] == 0 {
+                debug!("skipping surface configuration due to zero sized framebuffer: {size:?}");
+            } else if size == self.last_configured_size {
+                debug!("skipping surface configuration as the size didn't change: {size:?}");
+            } else {
+                self.surface_config.width = size[0];
+                self.surface_config.height = size[1];
+                self.surface
+                    .as_ref()
+                    .unwrap()
+                    .configure(&self.device, &self.surface_config);
+                self.last_configured_size = size;
+            }
         }
         assert!(self.surface_current_image.is_none());
         assert!(self.surface_view.is_none());
+        if !self.rendering_enabled || self.device_lost() {
+            return;
+        }
         if let Some(surface) = self.surface.as_ref() {
-            let current_surface_image = surface.get_current_texture().unwrap_or_else(|e| {
-                let phy_fb_size = window_backend.get_live_physical_size_framebuffer().unwrap();
-                self.surface_config.width = phy_fb_size[0];
-                self.surface_config.height = phy_fb_size[1];
-                surface.configure(&self.device, &self.surface_config);
-                surface.get_current_texture().expect(&format!(
-                    "failed to get surface even after reconfiguration. {e}"
-                ))
-            });
+            let current_surface_image = match surface.get_current_texture() {
+                Ok(current_surface_image) => current_surface_image,
+                Err(e) => {
+                    let phy_fb_size = window_backend.get_live_physical_size_framebuffer().unwrap();
+                    self.surface_config.width = phy_fb_size[0];
+                    self.surface_config.height = phy_fb_size[1];
+                    surface.configure(&self.device, &self.surface_config);
+                    self.last_configured_size = phy_fb_size;
+                    match surface.get_current_texture() {
+                        Ok(current_surface_image) => current_surface_image,
+                        // reconfiguring didn't help. so, the device is gone (eg: driver reset) and must be recreated
+                        Err(wgpu::SurfaceError::Lost) => {
+                            tracing::error!("surface is still lost after reconfiguration. treating the device as lost");
+                            self.device_lost.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                        Err(e2) => {
+                            panic!("failed to get surface even after reconfiguration. {e}. {e2}")
+                        }
+                    }
+                }
+            };
             let surface_view = current_surface_image
                 .texture
                 .create_view(&TextureViewDescriptor {
@@ -284,17 +2036,55 @@ impl<W: WindowBackend> GfxBackend<W> for WgpuBackend {
     }
 
     fn render(&mut self, egui_gfx_data: EguiGfxData) {
+        // the app is expected to recreate the backend (see `Self::device_lost`). so, we don't care about egui's textures anymore
+        let queued_layers = std::mem::take(&mut self.queued_layers);
+        if self.device_lost() {
+            return;
+        }
+        // the per frame spans are at debug/trace level. use tracing's `release_max_level_*` features to compile them out
+        let _render_span =
+            tracing::debug_span!("egui render", layers = queued_layers.len()).entered();
+        // `Self::render_to_target` might have switched the pipelines to a different format
+        self.painter
+            .on_resume(&self.device, self.surface_config.format);
+        // texture uploads are recorded into the same encoder as the render pass. so, there's a single submission per frame
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("egui command encoder"),
+            });
         self.painter.upload_egui_data(
             &self.device,
             &self.queue,
+            &mut command_encoder,
             egui_gfx_data,
             [self.surface_config.width, self.surface_config.height],
         );
-        let mut command_encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("egui command encoder"),
-            });
+        let mut layers_draw_order = Vec::with_capacity(queued_layers.len());
+        for (layer, layer_gfx_data) in queued_layers {
+            self.layers[layer].upload_egui_data(
+                &self.device,
+                &self.queue,
+                &mut command_encoder,
+                layer_gfx_data,
+                [self.surface_config.width, self.surface_config.height],
+            );
+            layers_draw_order.push(layer);
+        }
+        // we still uploaded egui data above, so that textures stay in sync with egui. the uploads are submitted by present
+        if !self.rendering_enabled {
+            self.command_encoders.push(command_encoder);
+            return;
+        }
+        // no surface (eg: headless, or before the window is created / after suspend). use `Self::render_to_target` to draw
+        // egui into your own texture instead.
+        if self.surface_view.is_none() {
+            debug!("skipping egui render pass as there's no surface view");
+            self.command_encoders.push(command_encoder);
+            return;
+        }
+        let depth_stencil_format = self.painter.depth_stencil_format();
+        self.depth_stencil_view([self.surface_config.width, self.surface_config.height]);
         {
             let mut egui_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("egui render pass"),
@@ -305,37 +2095,179 @@ impl<W: WindowBackend> GfxBackend<W> for WgpuBackend {
                         .expect("failed ot get surface view for egui render pass creation"),
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Load,
+                        load: match self.clear_color {
+                            Some(color) => LoadOp::Clear(color),
+                            None => LoadOp::Load,
+                        },
                         store: true,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: self
+                    .depth_stencil
+                    .as_ref()
+                    .zip(depth_stencil_format)
+                    .map(|(depth_stencil, format)| {
+                        depth_stencil_attachment(&depth_stencil.view, format)
+                    }),
             });
             self.painter.draw_egui_with_renderpass(&mut egui_pass);
+            for layer in layers_draw_order {
+                self.layers[layer].draw_egui_with_renderpass(&mut egui_pass);
+            }
         }
         self.command_encoders.push(command_encoder);
     }
 
     fn present(&mut self, _window_backend: &mut W) {
+        if self.device_lost() {
+            self.command_encoders.clear();
+            self.surface_view = None;
+            self.surface_current_image = None;
+            return;
+        }
+        // errors of the submission are reported to our uncaptured error handler (see `Self::register_error_handler`)
+        let _submit_span =
+            tracing::debug_span!("egui submit", command_buffers = self.command_encoders.len())
+                .entered();
         self.queue.submit(
             std::mem::take(&mut self.command_encoders)
                 .into_iter()
                 .map(|encoder| encoder.finish()),
         );
-        {
-            self.surface_view
-                .take()
-                .expect("failed to get surface view to present");
+        if self.poll_after_submit {
+            self.device.poll(Maintain::Wait);
         }
-        self.surface_current_image
-            .take()
-            .expect("failed to surface texture to preset")
-            .present();
+        if !self.rendering_enabled {
+            return;
+        }
+        // the view must be dropped before presenting
+        self.surface_view = None;
+        // without a surface (eg: headless), we only submit the command encoders
+        if let Some(surface_current_image) = self.surface_current_image.take() {
+            surface_current_image.present();
+        }
+    }
+
+    fn discard_frame(&mut self, _window_backend: &mut W) -> bool {
+        let queued_layers = std::mem::take(&mut self.queued_layers);
+        if self.device_lost() {
+            self.command_encoders.clear();
+            self.surface_view = None;
+            self.surface_current_image = None;
+            return true;
+        }
+        // layers are not tracked by the window backend. we still upload them, so that their textures stay in sync with egui
+        if !queued_layers.is_empty() {
+            let mut command_encoder =
+                self.device
+                    .create_command_encoder(&CommandEncoderDescriptor {
+                        label: Some("egui layer upload command encoder"),
+                    });
+            for (layer, layer_gfx_data) in queued_layers {
+                self.layers[layer].upload_egui_data(
+                    &self.device,
+                    &self.queue,
+                    &mut command_encoder,
+                    layer_gfx_data,
+                    [self.surface_config.width, self.surface_config.height],
+                );
+            }
+            self.command_encoders.push(command_encoder);
+        }
+        // user's command encoders (and layer uploads) might have work other than drawing into the surface
+        if !self.command_encoders.is_empty() {
+            self.queue.submit(
+                std::mem::take(&mut self.command_encoders)
+                    .into_iter()
+                    .map(|encoder| encoder.finish()),
+            );
+        }
+        // dropping the surface texture without presenting discards it. so, the previous frame stays on screen.
+        self.surface_view = None;
+        self.surface_current_image = None;
+        true
+    }
+}
+
+/// wgpu 0.14 has no dedicated device lost error or callback. a lost device shows up as a validation error whose source chain
+/// contains wgpu-core's `DeviceError::Lost` ("Parent device is lost"). wgpu doesn't re-export that type, so we match its message.
+/// a surface which is still lost after reconfiguration is treated as a lost device too (see `GfxBackend::prepare_frame`).
+/// out of memory errors don't mean that the device is lost.
+fn is_device_lost_error(e: &wgpu::Error) -> bool {
+    let source = match e {
+        wgpu::Error::OutOfMemory { .. } => return false,
+        wgpu::Error::Validation { source, .. } => source,
+    };
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(source.as_ref());
+    while let Some(error) = source {
+        if error.to_string().contains("device is lost") {
+            return true;
+        }
+        source = error.source();
     }
+    false
 }
 
 pub const EGUI_SHADER_SRC: &str = include_str!("../../../shaders/egui.wgsl");
 
+/// checks that a custom egui shader has the entry points and bindings that our pipelines rely on:
+/// `vs_main`, `fs_main`, the screen size uniform at `@group(0) @binding(0)` and the texture sampler / texture at
+/// `@group(1) @binding(0)` / `@group(1) @binding(1)`. `fs_main_srgb_texture` is optional (see `EguiPainter::set_custom_shader_src`).
+/// this is just a textual check. wgpu still validates the shader itself when the pipeline is created.
+pub fn validate_egui_shader_src(shader_src: &str) -> Result<(), String> {
+    // ignore whitespace differences like `@group(0)  @binding(0)` or `fn vs_main (`
+    let src: String = shader_src.split_whitespace().collect();
+    for (required, description) in [
+        ("fnvs_main(", "vertex entry point `vs_main`"),
+        ("fnfs_main(", "fragment entry point `fs_main`"),
+        (
+            "@group(0)@binding(0)",
+            "screen size uniform binding `@group(0) @binding(0)`",
+        ),
+        (
+            "@group(1)@binding(0)",
+            "texture sampler binding `@group(1) @binding(0)`",
+        ),
+        (
+            "@group(1)@binding(1)",
+            "texture binding `@group(1) @binding(1)`",
+        ),
+    ] {
+        if !src.contains(required) {
+            return Err(format!("shader is missing the {description}"));
+        }
+    }
+    Ok(())
+}
+
+/// converts an egui shader (`EGUI_SHADER_SRC` or a custom shader which passes `validate_egui_shader_src`) to read the screen size
+/// from a push constant: the `@group(0) @binding(0) var<uniform> u_screen_size: vec4<f32>;` declaration becomes
+/// `var<push_constant> u_screen_size: vec4<f32>;` and the texture bindings move from `@group(1)` to `@group(0)`,
+/// as the pipeline layout doesn't have the screen size bind group anymore. see `EguiPainter::set_screen_size_push_constants`.
+/// like `validate_egui_shader_src`, this is a textual (line based) conversion.
+pub fn screen_size_push_constant_shader_src(shader_src: &str) -> Result<String, String> {
+    let mut found_uniform = false;
+    let lines: Vec<String> = shader_src
+        .lines()
+        .map(|line| {
+            let stripped: String = line.split_whitespace().collect();
+            if stripped == "@group(0)@binding(0)var<uniform>u_screen_size:vec4<f32>;" {
+                found_uniform = true;
+                "var<push_constant> u_screen_size: vec4<f32>;".to_string()
+            } else {
+                line.replace("@group(1)", "@group(0)")
+            }
+        })
+        .collect();
+    if !found_uniform {
+        return Err(
+            "shader is missing the declaration `@group(0) @binding(0) var<uniform> u_screen_size: vec4<f32>;`"
+                .to_string(),
+        );
+    }
+    Ok(lines.join("\n"))
+}
+
 type PrepareCallback = dyn Fn(&Device, &Queue, &mut IdTypeMap) + Sync + Send;
 type RenderCallback =
     dyn for<'a, 'b> Fn(PaintCallbackInfo, &'a mut RenderPass<'b>, &'b IdTypeMap) + Sync + Send;
@@ -354,6 +2286,8 @@ impl Default for CallbackFn {
     }
 }
 
+/// egui specific wgpu data: pipelines, vertex / index buffers, samplers and textures.
+/// `WgpuBackend` uses this to draw egui. see `WgpuBackend::painter_mut` to use it directly with your own render pass.
 pub struct EguiPainter {
     /// current capacity of vertex buffer
     vb_len: usize,
@@ -363,7 +2297,8 @@ pub struct EguiPainter {
     vb: Buffer,
     /// index buffer
     ib: Buffer,
-    /// Uniform buffer to store screen size in logical pixels
+    /// Uniform buffer to store screen size in logical pixels. 16 bytes, as the shader reads it as a `vec4<f32>`
+    /// (uniforms are 16 byte aligned anyway): `[width, height, 0.0, 0.0]`. the last two are padding for now and always zero.
     screen_size_buffer: Buffer,
     /// bind group for the Uniform buffer using layout entry `SCREEN_SIZE_UNIFORM_BUFFER_BINDGROUP_ENTRY`
     screen_size_bind_group: BindGroup,
@@ -371,33 +2306,365 @@ pub struct EguiPainter {
     texture_bindgroup_layout: BindGroupLayout,
     /// used by pipeline create function
     screen_size_bindgroup_layout: BindGroupLayout,
-    /// used to check if this matches the new surface after resume event. otherwise, recompile render pipeline
+    /// if true, the pipelines read the screen size from a push constant instead of `Self::screen_size_buffer`.
+    /// set with `Self::set_screen_size_push_constants`
+    screen_size_push_constants: bool,
+    /// format of the render pass's depth-stencil attachment. set with `Self::set_depth_stencil_format`
+    depth_stencil_format: Option<TextureFormat>,
+    /// if true, meshes are written into `MappableMeshBuffer`s instead of `Self::vb` / `Self::ib`.
+    /// set with `Self::set_mappable_mesh_buffers`
+    mappable_mesh_buffers: bool,
+    /// mappable mesh buffers which were mapped again after the gpu was done with them. shared with the map callbacks
+    free_mappable_mesh_buffers: Arc<std::sync::Mutex<Vec<Arc<MappableMeshBuffer>>>>,
+    /// the mappable mesh buffer used by the current draw calls. mapped again in the next `Self::upload_egui_data`
+    current_mappable_mesh_buffer: Option<Arc<MappableMeshBuffer>>,
+    /// mappable mesh buffers that exist (free, in flight or current). capped at `MAX_MAPPABLE_MESH_BUFFERS`
+    mappable_mesh_buffers_count: usize,
+    /// `[width, height, 0.0, 0.0]` of the current frame. set in `Self::upload_egui_data`, uploaded with either
+    /// `Queue::write_buffer` or `RenderPass::set_push_constants` (see `Self::screen_size_push_constants`)
+    screen_size: [f32; 4],
+    /// format of the color attachment that egui is drawn into. selects the pipelines in `Self::pipelines`. set by `Self::on_resume`
     surface_format: TextureFormat,
-    /// egui render pipeline
-    pipeline: RenderPipeline,
+    /// egui's pipelines for every color attachment format used so far (eg: the surface and an offscreen target),
+    /// so that switching between formats doesn't rebuild them every frame. cleared when the pipeline settings
+    /// (shader, push constants or depth-stencil format) change.
+    pipelines: HashMap<TextureFormat, EguiPipelines>,
     /// linear sampler for egui textures that need to create bindgroups
     linear_sampler: Sampler,
+    /// anisotropy clamp of the linear sampler
+    linear_sampler_anisotropy: Option<NonZeroU8>,
     /// nearest sampler for egui textures (especially font texture) that need to create bindgroups for binding to egui pipelien
     nearest_sampler: Sampler,
+    /// shader used instead of `EGUI_SHADER_SRC`. set with `Self::set_custom_shader_src`
+    custom_shader_src: Option<String>,
 
     /// these are textures uploaded by egui. intmap is much faster than btree or hashmaps.
     /// maybe we can use a proper struct instead of tuple?
     managed_textures: IntMap<EguiTexture>,
-    #[allow(unused)]
-    user_textures: IntMap<EguiTexture>,
+    /// textures registered by the user with `Self::register_native_texture` or `Self::register_native_texture_with_id`
+    user_textures: IntMap<UserTexture>,
+    /// id to be used by the next `Self::register_native_texture` call
+    next_user_texture_id: u64,
     /// textures to free
     delete_textures: Vec<TextureId>,
+    /// user textures of dropped `ImageHandle`s. moved into `Self::delete_textures` by `Self::upload_egui_data`
+    dropped_user_textures: Arc<std::sync::Mutex<Vec<TextureId>>>,
+    /// used by `Self::set_textures` to batch texture uploads
+    texture_upload_belt: TextureUploadBelt,
     draw_calls: Vec<EguiDrawCalls>,
+    /// `EguiGfxData::viewport` of the current frame, or the full render target if that was `None`. set in `Self::upload_egui_data`
+    viewport: Option<[f32; 4]>,
+    /// set in `Self::upload_egui_data`. see `Self::last_frame_stats`
+    last_frame_stats: FrameStats,
     custom_data: IdTypeMap,
 }
 
+/// egui's pipelines for one color attachment format. see `EguiPainter::pipelines`
+struct EguiPipelines {
+    /// egui render pipeline
+    pipeline: RenderPipeline,
+    /// same as `Self::pipeline`, but decodes srgb colors of the texture. used for user textures registered with `linear_view`
+    srgb_texture_pipeline: RenderPipeline,
+}
+
+/// writes tightly packed rgba pixels into the whole (first mip of the) texture
+fn write_image_texture(queue: &Queue, texture: &Texture, rgba: &[u8], width: u32, height: u32) {
+    queue.write_texture(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::default(),
+            aspect: TextureAspect::All,
+        },
+        rgba,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(width * 4),
+            rows_per_image: NonZeroU32::new(height),
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+/// an image registered with `WgpuBackend::register_image`. use `Self::id` with egui (eg: `ui.image(handle.id(), size)`).
+/// dropping it frees the user texture during the next `EguiPainter::upload_egui_data`, just like `EguiPainter::free_user_texture`.
+/// so, don't use the id after dropping the handle.
+pub struct ImageHandle {
+    id: TextureId,
+    texture: Texture,
+    size: [u32; 2],
+    filter: egui::TextureFilter,
+    /// shared with the painter which registered the texture. see `EguiPainter::dropped_user_textures`
+    dropped_user_textures: Arc<std::sync::Mutex<Vec<TextureId>>>,
+}
+impl ImageHandle {
+    /// the user texture id. stays the same across `Self::update` calls
+    pub fn id(&self) -> TextureId {
+        self.id
+    }
+    /// size of the image in pixels
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+    /// replaces the image with new tightly packed (srgb encoded) rgba pixels, keeping the same `TextureId`.
+    /// if the size is the same, the pixels are written into the existing texture. otherwise, a new texture is created and
+    /// registered with the same id.
+    pub fn update(&mut self, backend: &mut WgpuBackend, rgba: &[u8], width: u32, height: u32) {
+        if self.size == [width, height] {
+            assert_eq!(
+                rgba.len(),
+                width as usize * height as usize * 4,
+                "rgba pixels length doesn't match the image size {width}x{height}"
+            );
+            write_image_texture(&backend.queue, &self.texture, rgba, width, height);
+            return;
+        }
+        let user_id = match self.id {
+            TextureId::User(user_id) => user_id,
+            TextureId::Managed(_) => unreachable!("image handles always have user texture ids"),
+        };
+        self.texture = backend.create_image_texture(rgba, width, height);
+        let view = self.texture.create_view(&TextureViewDescriptor::default());
+        backend.register_native_texture_with_id(user_id, &view, self.filter, false);
+        self.size = [width, height];
+    }
+    /// like `Self::update`, but for straight (non-premultiplied) alpha. see `premultiply_rgba`
+    pub fn update_unmultiplied(
+        &mut self,
+        backend: &mut WgpuBackend,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) {
+        self.update(backend, &premultiply_rgba(rgba), width, height);
+    }
+}
+/// premultiplies srgb encoded rgba pixels with straight alpha (like most decoded pngs) on the cpu, the same way egui's
+/// `Color32::from_rgba_unmultiplied` does. egui blends textures as premultiplied, so straight alpha shows dark edges.
+/// use this before uploading into your own textures too (eg: for `EguiPainter::register_native_texture`).
+pub fn premultiply_rgba(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|pixel| {
+            let color =
+                egui::Color32::from_rgba_unmultiplied(pixel[0], pixel[1], pixel[2], pixel[3]);
+            [color[0], color[1], color[2], color[3]]
+        })
+        .collect()
+}
+impl Drop for ImageHandle {
+    fn drop(&mut self) {
+        self.dropped_user_textures
+            .lock()
+            .expect("failed to lock dropped user textures")
+            .push(self.id);
+    }
+}
 /// textures uploaded by egui are represented by this struct
 pub struct EguiTexture {
     pub texture: Texture,
     pub view: TextureView,
     pub bindgroup: BindGroup,
+    /// filter of the sampler used in the bindgroup. if egui changes it, we recreate the bindgroup.
+    /// always `Nearest` for the font texture.
+    pub filter: egui::TextureFilter,
+    /// size of `Self::texture` in pixels. if egui sends a full update with the same size, we reuse the texture
+    pub size: [u32; 2],
+    /// format of `Self::texture` and `Self::view`
+    pub format: TextureFormat,
+    /// true if the texture contains srgb encoded colors, which are decoded by the gpu (`*Srgb` format) while sampling
+    pub srgb: bool,
+}
+/// textures registered by the user are represented by this struct.
+/// the texture view is owned by the user, we only keep the bindgroup which keeps it alive.
+pub struct UserTexture {
+    pub bindgroup: BindGroup,
+    /// if true, the view has a non-srgb format with srgb encoded data. so, we draw it with `EguiPipelines::srgb_texture_pipeline`
+    pub linear_view: bool,
+}
+/// batches egui texture uploads of a frame into a few big staging buffers (chunks) and records the copies into the frame's
+/// command encoder, instead of a separate `Queue::write_texture` for each texture.
+/// chunks are mapped again once the frame was submitted and reused in the next frames.
+/// uploads bigger than a chunk fall back to `Queue::write_texture`.
+pub struct TextureUploadBelt {
+    /// size of each staging buffer in bytes
+    chunk_size: u64,
+    /// chunk that we are currently writing into and the offset of its free space
+    active_chunk: Option<(Arc<Buffer>, u64)>,
+    /// chunks that are full, but not submitted yet
+    closed_chunks: Vec<Arc<Buffer>>,
+    /// chunks submitted to the gpu, which will be pushed into `Self::free_chunks` once they are mapped again.
+    free_chunks: Arc<std::sync::Mutex<Vec<Arc<Buffer>>>>,
+}
+impl TextureUploadBelt {
+    /// 4 MB is enough for the font texture and a few images
+    pub const DEFAULT_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+    pub fn new(chunk_size: u64) -> Self {
+        Self {
+            chunk_size,
+            active_chunk: None,
+            closed_chunks: Vec::new(),
+            free_chunks: Default::default(),
+        }
+    }
+    /// records a copy of `pixels` (tightly packed RGBA8 rows) into `texture` at `origin` using the staging buffers.
+    /// call `Self::finish` before submitting the encoder and `Self::recall` after submitting it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload(
+        &mut self,
+        dev: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        origin: Origin3d,
+        pixels: &[u8],
+        size: [u32; 2],
+    ) {
+        let unpadded_bytes_per_row = size[0] * 4;
+        let padded_bytes_per_row = padded_bytes_per_row(size[0]);
+        let upload_size = padded_bytes_per_row as u64 * size[1] as u64;
+        let extent = Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        };
+        if upload_size == 0 {
+            return;
+        }
+        if upload_size > self.chunk_size {
+            debug!("texture upload of {upload_size} bytes is bigger than the staging chunk. using write_texture");
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin,
+                    aspect: TextureAspect::All,
+                },
+                pixels,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(unpadded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(size[1]),
+                },
+                extent,
+            );
+            return;
+        }
+        // close the active chunk if it doesn't have enough space left
+        if let Some((chunk, offset)) = self.active_chunk.take() {
+            if offset + upload_size <= self.chunk_size {
+                self.active_chunk = Some((chunk, offset));
+            } else {
+                self.closed_chunks.push(chunk);
+            }
+        }
+        let (chunk, offset) = self.active_chunk.get_or_insert_with(|| {
+            // reuse a chunk which is already mapped. otherwise, create a new one which is mapped at creation
+            let free_chunk = self
+                .free_chunks
+                .lock()
+                .expect("failed to lock free texture upload chunks")
+                .pop();
+            let chunk = free_chunk.unwrap_or_else(|| {
+                Arc::new(dev.create_buffer(&BufferDescriptor {
+                    label: Some("egui texture upload chunk"),
+                    size: self.chunk_size,
+                    usage: BufferUsages::MAP_WRITE | BufferUsages::COPY_SRC,
+                    mapped_at_creation: true,
+                }))
+            });
+            (chunk, 0)
+        });
+        {
+            let mut mapped = chunk
+                .slice(*offset..*offset + upload_size)
+                .get_mapped_range_mut();
+            for (dst, src) in mapped
+                .chunks_exact_mut(padded_bytes_per_row as usize)
+                .zip(pixels.chunks_exact(unpadded_bytes_per_row as usize))
+            {
+                dst[..unpadded_bytes_per_row as usize].copy_from_slice(src);
+            }
+        }
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: chunk,
+                layout: ImageDataLayout {
+                    offset: *offset,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(size[1]),
+                },
+            },
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: TextureAspect::All,
+            },
+            extent,
+        );
+        // padded bytes per row is a multiple of 256, so the next offset is aligned too
+        *offset += upload_size;
+    }
+    /// unmaps the chunks used in this frame. must be called before submitting the encoder.
+    pub fn finish(&mut self) {
+        if let Some((chunk, _)) = self.active_chunk.take() {
+            self.closed_chunks.push(chunk);
+        }
+        for chunk in self.closed_chunks.iter() {
+            chunk.unmap();
+        }
+    }
+    /// maps the used chunks again, so that they can be reused once the gpu is done with them.
+    /// must be called after submitting the encoder.
+    pub fn recall(&mut self) {
+        for chunk in self.closed_chunks.drain(..) {
+            let free_chunks = self.free_chunks.clone();
+            let mapped_chunk = chunk.clone();
+            chunk
+                .slice(..)
+                .map_async(wgpu::MapMode::Write, move |result| match result {
+                    Ok(_) => free_chunks
+                        .lock()
+                        .expect("failed to lock free texture upload chunks")
+                        .push(mapped_chunk),
+                    Err(e) => tracing::error!("failed to map texture upload chunk: {e}"),
+                });
+        }
+    }
+}
+/// a buffer with the indices (first) and the vertices (from `Self::vertex_offset`) of a frame, written through a mapping
+/// instead of a staging copy. see `EguiPainter::set_mappable_mesh_buffers`
+struct MappableMeshBuffer {
+    buffer: Buffer,
+    /// byte offset of the vertices. it's the size of the indices, so it is aligned to 4 bytes
+    vertex_offset: u64,
+    /// capacity in vertices
+    vertices: usize,
+    /// capacity in indices
+    indices: usize,
+}
+/// mappable mesh buffers that a painter keeps around. usually, a frame or two is in flight at a time.
+pub const MAX_MAPPABLE_MESH_BUFFERS: usize = 3;
+/// how heavy the egui output of a frame was. recorded by `EguiPainter::upload_egui_data`. see `EguiPainter::last_frame_stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// vertices uploaded into the vertex buffer
+    pub vertices: usize,
+    /// indices uploaded into the index buffer
+    pub indices: usize,
+    /// mesh draw calls and paint callbacks. meshes with an empty clip rect are skipped, so they are not counted
+    pub draw_calls: usize,
+    /// textures created or updated (partially or fully). deltas which were ignored (eg: for user textures) are not counted
+    pub textures_uploaded: usize,
+    /// textures that egui asked to free. they are actually freed in the next frame, once they are no longer in use
+    pub textures_freed: usize,
 }
 /// DrawCalls list so that we can just get all the work done in the pre_render stage (upload egui data)
+/// `clip_rect` is the scissor rect `[x, y, width, height]` in physical pixels. `base_vertex` and the index range are
+/// offsets into the painter's vertex and index buffers. see `EguiPainter::draw_calls`
 pub enum EguiDrawCalls {
     Mesh {
         clip_rect: [u32; 4],
@@ -412,14 +2679,94 @@ pub enum EguiDrawCalls {
         paint_callback: PaintCallback,
     },
 }
+// `PaintCallbackInfo` doesn't implement `Debug`. so, we print its fields ourselves
+impl std::fmt::Debug for EguiDrawCalls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mesh {
+                clip_rect,
+                texture_id,
+                base_vertex,
+                index_start,
+                index_end,
+            } => f
+                .debug_struct("Mesh")
+                .field("clip_rect", clip_rect)
+                .field("texture_id", texture_id)
+                .field("base_vertex", base_vertex)
+                .field("index_start", index_start)
+                .field("index_end", index_end)
+                .finish(),
+            Self::Callback {
+                paint_callback_info,
+                clip_rect,
+                paint_callback,
+            } => f
+                .debug_struct("Callback")
+                .field("viewport", &paint_callback_info.viewport)
+                .field("callback_clip_rect", &paint_callback_info.clip_rect)
+                .field("pixels_per_point", &paint_callback_info.pixels_per_point)
+                .field("screen_size_px", &paint_callback_info.screen_size_px)
+                .field("clip_rect", clip_rect)
+                .field("paint_callback", paint_callback)
+                .finish(),
+        }
+    }
+}
 impl EguiPainter {
-    pub fn draw_egui_with_renderpass<'rpass>(&'rpass mut self, rpass: &mut RenderPass<'rpass>) {
-        // rpass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
-        rpass.set_pipeline(&self.pipeline);
-        rpass.set_bind_group(0, &self.screen_size_bind_group, &[]);
+    /// the draw calls prepared by the last `Self::upload_egui_data`, in draw order.
+    /// useful for debugging overlays or to dump the draw list of a frame (`EguiDrawCalls` implements `Debug`).
+    pub fn draw_calls(&self) -> &[EguiDrawCalls] {
+        &self.draw_calls
+    }
+    /// vertex / index / draw call / texture counts of the last `Self::upload_egui_data`.
+    /// useful to log or display a warning when a frame goes over a budget (eg: too many vertices for a low end gpu).
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.last_frame_stats
+    }
+    /// records the egui draw calls prepared by the last `Self::upload_egui_data` into the render pass.
+    /// the color attachment must use the format that the painter was created with (the surface format).
+    pub fn draw_egui_with_renderpass<'rpass>(&'rpass self, rpass: &mut RenderPass<'rpass>) {
+        let _draw_span = tracing::trace_span!(
+            "egui draw call recording",
+            draw_calls = self.draw_calls.len()
+        )
+        .entered();
+        // always set our own viewport (letterboxed virtual screen or the full target), because the render pass might be shared
+        // with other painters (eg: layers) which set a different one. scissor rects already include the viewport offset
+        if let Some([x, y, width, height]) = self.viewport {
+            if width > 0.0 && height > 0.0 {
+                rpass.set_viewport(x, y, width, height, 0.0, 1.0);
+            }
+        }
+        let pipelines = self
+            .pipelines
+            .get(&self.surface_format)
+            .expect("pipelines of the current format are created by on_resume");
+        rpass.set_pipeline(&pipelines.pipeline);
+        // we only switch pipelines when a user texture needs srgb decoding
+        let mut srgb_texture_pipeline_bound = false;
+        // without the screen size bind group, the texture bind group moves to index 0. see `screen_size_push_constant_shader_src`
+        let texture_bind_group_index = if self.screen_size_push_constants {
+            rpass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&self.screen_size));
+            0
+        } else {
+            rpass.set_bind_group(0, &self.screen_size_bind_group, &[]);
+            1
+        };
 
-        rpass.set_vertex_buffer(0, self.vb.slice(..));
-        rpass.set_index_buffer(self.ib.slice(..), IndexFormat::Uint32);
+        // a mappable mesh buffer has the indices first and the vertices after them. see `Self::set_mappable_mesh_buffers`
+        let (vertex_buffer, vertex_offset, index_buffer_slice) =
+            match self.current_mappable_mesh_buffer.as_deref() {
+                Some(mesh_buffer) => (
+                    &mesh_buffer.buffer,
+                    mesh_buffer.vertex_offset,
+                    mesh_buffer.buffer.slice(..mesh_buffer.vertex_offset),
+                ),
+                None => (&self.vb, 0, self.ib.slice(..)),
+            };
+        rpass.set_vertex_buffer(0, vertex_buffer.slice(vertex_offset..));
+        rpass.set_index_buffer(index_buffer_slice, IndexFormat::Uint32);
         for draw_call in self.draw_calls.iter() {
             match draw_call {
                 &EguiDrawCalls::Mesh {
@@ -433,21 +2780,36 @@ impl EguiPainter {
                     rpass.set_scissor_rect(x, y, width, height);
                     // because webgl : Draw elements base vertex is not supported
                     // we can't use base_vertex argument of draw_indexed. we will make sure that bound vertex buffer starts from base_vertex at zero.
-                    rpass.set_vertex_buffer(0, self.vb.slice(base_vertex as u64 * 20..));
-                    match texture_id {
-                        TextureId::Managed(key) => {
-                            rpass.set_bind_group(
-                                1,
-                                &self
-                                    .managed_textures
-                                    .get(key)
-                                    .expect("cannot find managed texture")
-                                    .bindgroup,
-                                &[],
-                            );
+                    rpass.set_vertex_buffer(
+                        0,
+                        vertex_buffer.slice(vertex_offset + base_vertex as u64 * 20..),
+                    );
+                    let (bindgroup, linear_view) = match texture_id {
+                        TextureId::Managed(key) => (
+                            &self
+                                .managed_textures
+                                .get(key)
+                                .expect("cannot find managed texture")
+                                .bindgroup,
+                            false,
+                        ),
+                        TextureId::User(key) => {
+                            let user_texture = self
+                                .user_textures
+                                .get(key)
+                                .expect("cannot find user texture");
+                            (&user_texture.bindgroup, user_texture.linear_view)
                         }
-                        TextureId::User(_) => unimplemented!(),
+                    };
+                    if linear_view != srgb_texture_pipeline_bound {
+                        rpass.set_pipeline(if linear_view {
+                            &pipelines.srgb_texture_pipeline
+                        } else {
+                            &pipelines.pipeline
+                        });
+                        srgb_texture_pipeline_bound = linear_view;
                     }
+                    rpass.set_bind_group(texture_bind_group_index, bindgroup, &[]);
                     rpass.draw_indexed(index_start..index_end, 0, 0..1);
                 }
                 EguiDrawCalls::Callback {
@@ -475,43 +2837,128 @@ impl EguiPainter {
             }
         }
     }
-    pub fn create_render_pipeline(
+    pub fn create_render_pipeline(
+        dev: &Device,
+        pipeline_surface_format: TextureFormat,
+        screen_size_bindgroup_layout: &BindGroupLayout,
+        texture_bindgroup_layout: &BindGroupLayout,
+    ) -> RenderPipeline {
+        Self::create_render_pipeline_with_fragment_entry_point(
+            dev,
+            pipeline_surface_format,
+            screen_size_bindgroup_layout,
+            texture_bindgroup_layout,
+            "fs_main",
+        )
+    }
+    /// same as `Self::create_render_pipeline`, but with the provided fragment shader entry point of `EGUI_SHADER_SRC`.
+    /// `fs_main` for regular egui textures and `fs_main_srgb_texture` for textures which need srgb decoding.
+    pub fn create_render_pipeline_with_fragment_entry_point(
         dev: &Device,
         pipeline_surface_format: TextureFormat,
         screen_size_bindgroup_layout: &BindGroupLayout,
         texture_bindgroup_layout: &BindGroupLayout,
+        fragment_entry_point: &str,
+    ) -> RenderPipeline {
+        Self::create_render_pipeline_with_shader(
+            dev,
+            pipeline_surface_format,
+            screen_size_bindgroup_layout,
+            texture_bindgroup_layout,
+            EGUI_SHADER_SRC,
+            fragment_entry_point,
+            None,
+        )
+    }
+    /// same as `Self::create_render_pipeline_with_fragment_entry_point`, but with the provided wgsl source instead of `EGUI_SHADER_SRC`.
+    /// the source must pass `validate_egui_shader_src`. `depth_stencil_format` is the format of the render pass's depth-stencil
+    /// attachment (if any). egui doesn't test or write depth / stencil. see `EguiPainter::set_depth_stencil_format`
+    pub fn create_render_pipeline_with_shader(
+        dev: &Device,
+        pipeline_surface_format: TextureFormat,
+        screen_size_bindgroup_layout: &BindGroupLayout,
+        texture_bindgroup_layout: &BindGroupLayout,
+        shader_src: &str,
+        fragment_entry_point: &str,
+        depth_stencil_format: Option<TextureFormat>,
     ) -> RenderPipeline {
-        assert!(
-            pipeline_surface_format.describe().srgb,
-            "egui wgpu only supports srgb compatible framebuffer"
-        );
         // pipeline layout. screensize uniform buffer for vertex shader + texture and sampler for fragment shader
         let egui_pipeline_layout = dev.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("egui pipeline layout"),
             bind_group_layouts: &[screen_size_bindgroup_layout, texture_bindgroup_layout],
             push_constant_ranges: &[],
         });
+        Self::create_render_pipeline_with_layout(
+            dev,
+            pipeline_surface_format,
+            &egui_pipeline_layout,
+            shader_src,
+            fragment_entry_point,
+            depth_stencil_format,
+        )
+    }
+    /// same as `Self::create_render_pipeline_with_shader`, but the screen size is a 16 byte vertex stage push constant
+    /// and the texture bindgroup is at index 0. the source must be converted with `screen_size_push_constant_shader_src`.
+    /// the device needs `Features::PUSH_CONSTANTS`. see `Self::set_screen_size_push_constants`
+    pub fn create_push_constant_render_pipeline(
+        dev: &Device,
+        pipeline_surface_format: TextureFormat,
+        texture_bindgroup_layout: &BindGroupLayout,
+        shader_src: &str,
+        fragment_entry_point: &str,
+        depth_stencil_format: Option<TextureFormat>,
+    ) -> RenderPipeline {
+        // pipeline layout. screensize push constant for vertex shader + texture and sampler for fragment shader
+        let egui_pipeline_layout = dev.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("egui push constant pipeline layout"),
+            bind_group_layouts: &[texture_bindgroup_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..16,
+            }],
+        });
+        Self::create_render_pipeline_with_layout(
+            dev,
+            pipeline_surface_format,
+            &egui_pipeline_layout,
+            shader_src,
+            fragment_entry_point,
+            depth_stencil_format,
+        )
+    }
+    fn create_render_pipeline_with_layout(
+        dev: &Device,
+        pipeline_surface_format: TextureFormat,
+        egui_pipeline_layout: &wgpu::PipelineLayout,
+        shader_src: &str,
+        fragment_entry_point: &str,
+        depth_stencil_format: Option<TextureFormat>,
+    ) -> RenderPipeline {
+        assert!(
+            pipeline_surface_format.describe().srgb,
+            "egui wgpu only supports srgb compatible framebuffer"
+        );
         // shader from the wgsl source.
         let shader_module = dev.create_shader_module(ShaderModuleDescriptor {
             label: Some("egui shader src"),
-            source: ShaderSource::Wgsl(EGUI_SHADER_SRC.into()),
+            source: ShaderSource::Wgsl(shader_src.into()),
         });
         // create pipeline using shaders + pipeline layout
         let egui_pipeline = dev.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("egui pipeline"),
-            layout: Some(&egui_pipeline_layout),
+            layout: Some(egui_pipeline_layout),
             vertex: VertexState {
                 module: &shader_module,
                 entry_point: "vs_main",
                 buffers: &VERTEX_BUFFER_LAYOUT,
             },
             primitive: EGUI_PIPELINE_PRIMITIVE_STATE,
-            depth_stencil: None,
+            depth_stencil: depth_stencil_format.map(egui_pipeline_depth_stencil_state),
             // support multi sampling in future?
             multisample: MultisampleState::default(),
             fragment: Some(FragmentState {
                 module: &shader_module,
-                entry_point: "fs_main",
+                entry_point: fragment_entry_point,
                 targets: &[Some(ColorTargetState {
                     format: pipeline_surface_format,
                     blend: Some(EGUI_PIPELINE_BLEND_STATE),
@@ -562,6 +3009,13 @@ impl EguiPainter {
             &screen_size_bindgroup_layout,
             &texture_bindgroup_layout,
         );
+        let srgb_texture_pipeline = Self::create_render_pipeline_with_fragment_entry_point(
+            dev,
+            surface_format,
+            &screen_size_bindgroup_layout,
+            &texture_bindgroup_layout,
+            "fs_main_srgb_texture",
+        );
         // linear and nearest samplers for egui textures to use for creation of their bindgroups
         let linear_sampler = dev.create_sampler(&EGUI_LINEAR_SAMPLER_DESCRIPTOR);
         let nearest_sampler = dev.create_sampler(&EGUI_NEAREST_SAMPLER_DESCRIPTOR);
@@ -582,9 +3036,17 @@ impl EguiPainter {
 
         Self {
             screen_size_buffer,
-            pipeline,
+            pipelines: HashMap::from([(
+                surface_format,
+                EguiPipelines {
+                    pipeline,
+                    srgb_texture_pipeline,
+                },
+            )]),
             linear_sampler,
+            linear_sampler_anisotropy: None,
             nearest_sampler,
+            custom_shader_src: None,
             managed_textures: Default::default(),
             vb,
             ib,
@@ -593,32 +3055,457 @@ impl EguiPainter {
             vb_len: 0,
             ib_len: 0,
             delete_textures: Vec::new(),
+            dropped_user_textures: Default::default(),
+            texture_upload_belt: TextureUploadBelt::new(TextureUploadBelt::DEFAULT_CHUNK_SIZE),
             draw_calls: Vec::new(),
+            viewport: None,
+            last_frame_stats: FrameStats::default(),
             custom_data: IdTypeMap::default(),
             user_textures: Default::default(),
+            next_user_texture_id: 0,
             screen_size_bindgroup_layout,
+            screen_size_push_constants: false,
+            depth_stencil_format: None,
+            mappable_mesh_buffers: false,
+            free_mappable_mesh_buffers: Default::default(),
+            current_mappable_mesh_buffer: None,
+            mappable_mesh_buffers_count: 0,
+            screen_size: [0.0; 4],
             surface_format,
         }
     }
-    fn on_resume(&mut self, dev: &Device, surface_format: TextureFormat) {
-        if self.surface_format != surface_format {
-            self.pipeline = Self::create_render_pipeline(
-                dev,
-                surface_format,
-                &self.screen_size_bindgroup_layout,
-                &self.texture_bindgroup_layout,
+    /// recreates the linear sampler with the provided anisotropy clamp.
+    /// only textures created after this call will use the new sampler, so call this before the first frame.
+    /// the caller must make sure that the clamp is valid and supported by the adapter.
+    pub fn set_linear_sampler_anisotropy(
+        &mut self,
+        dev: &Device,
+        anisotropy_clamp: Option<NonZeroU8>,
+    ) {
+        self.linear_sampler = dev.create_sampler(&SamplerDescriptor {
+            anisotropy_clamp,
+            ..EGUI_LINEAR_SAMPLER_DESCRIPTOR
+        });
+        self.linear_sampler_anisotropy = anisotropy_clamp;
+    }
+    /// makes sure that vertex and index buffers can hold at least `vertices` and `indices` respectively.
+    /// buffers only grow, so you can call this at startup to reserve capacity and avoid reallocations during the first frames.
+    pub fn preallocate(&mut self, dev: &Device, vertices: usize, indices: usize) {
+        if self.vb_len < vertices {
+            self.vb = dev.create_buffer(&BufferDescriptor {
+                label: Some("egui vertex buffer"),
+                size: vertices as u64 * 20,
+                usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
+                mapped_at_creation: false,
+            });
+            self.vb_len = vertices;
+        }
+        if self.ib_len < indices {
+            self.ib = dev.create_buffer(&BufferDescriptor {
+                label: Some("egui index buffer"),
+                size: indices as u64 * 4,
+                usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
+                mapped_at_creation: false,
+            });
+            self.ib_len = indices;
+        }
+    }
+    /// registers a texture view created by the user, so that it can be used in egui with the returned `TextureId`.
+    /// every call returns a new id. if you want to update the texture behind an id, use `Self::register_native_texture_with_id`.
+    /// the view must be a `D2` view of a filterable float texture, to match egui's texture bindgroup layout.
+    ///
+    /// egui expects colors to be decoded to linear space when sampled, which the gpu does for `*Srgb` formats
+    /// (eg: `Rgba8UnormSrgb`). set `linear_view` to true if the view has the non-srgb variant of such a format
+    /// (eg: `Rgba8Unorm`, `Bgra8Unorm`, `Bc1RgbaUnorm` etc..) but contains srgb encoded colors (like most images),
+    /// and we will decode them in the shader instead. otherwise, the texture will look too bright / washed out.
+    /// formats without an srgb variant (eg: `Rgba16Float`, `Rgba32Float`) already store linear colors and don't need it.
+    pub fn register_native_texture(
+        &mut self,
+        dev: &Device,
+        view: &TextureView,
+        filter: egui::TextureFilter,
+        linear_view: bool,
+    ) -> TextureId {
+        let id = self.next_user_texture_id;
+        self.register_native_texture_with_id(dev, id, view, filter, linear_view)
+    }
+    /// same as `Self::register_native_texture`, but uses the provided `user_id`. if there's already a texture registered with this id,
+    /// it will be replaced. useful for textures which need to be re-registered often (eg: a camera feed), as the id stays stable.
+    pub fn register_native_texture_with_id(
+        &mut self,
+        dev: &Device,
+        user_id: u64,
+        view: &TextureView,
+        filter: egui::TextureFilter,
+        linear_view: bool,
+    ) -> TextureId {
+        let bindgroup =
+            self.create_texture_bindgroup(dev, "egui user texture bindgroup", view, filter);
+        self.insert_user_texture(user_id, bindgroup, linear_view)
+    }
+    /// like `Self::register_native_texture`, but the caller fully controls the view (eg: a single layer of a texture array
+    /// or a specific mip level of an atlas page) and the sampler (address modes, lod clamps, anisotropy etc..).
+    ///
+    /// the view must still match `TEXTURE_BINDGROUP_ENTRIES`: `TextureViewDimension::D2` (use `base_array_layer` and
+    /// `array_layer_count: NonZeroU32::new(1)` to view a single layer of an array) with a filterable float format.
+    /// the sampler must be a filtering sampler, so `compare` must be `None`.
+    pub fn register_native_texture_view(
+        &mut self,
+        dev: &Device,
+        view: &TextureView,
+        sampler_descriptor: &SamplerDescriptor,
+        linear_view: bool,
+    ) -> TextureId {
+        let id = self.next_user_texture_id;
+        self.register_native_texture_view_with_id(dev, id, view, sampler_descriptor, linear_view)
+    }
+    /// same as `Self::register_native_texture_view`, but uses the provided `user_id`. replaces the texture registered with this id.
+    pub fn register_native_texture_view_with_id(
+        &mut self,
+        dev: &Device,
+        user_id: u64,
+        view: &TextureView,
+        sampler_descriptor: &SamplerDescriptor,
+        linear_view: bool,
+    ) -> TextureId {
+        // the bindgroup keeps the sampler alive
+        let sampler = dev.create_sampler(sampler_descriptor);
+        let bindgroup = dev.create_bind_group(&BindGroupDescriptor {
+            label: Some("egui user texture view bindgroup"),
+            layout: &self.texture_bindgroup_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(view),
+                },
+            ],
+        });
+        self.insert_user_texture(user_id, bindgroup, linear_view)
+    }
+    fn insert_user_texture(
+        &mut self,
+        user_id: u64,
+        bindgroup: BindGroup,
+        linear_view: bool,
+    ) -> TextureId {
+        self.user_textures.insert(
+            user_id,
+            UserTexture {
+                bindgroup,
+                linear_view,
+            },
+        );
+        // make sure that auto generated ids never collide with user chosen ids.
+        // `user_id` is chosen by the caller, so it might be `u64::MAX`
+        self.next_user_texture_id = self.next_user_texture_id.max(user_id.saturating_add(1));
+        TextureId::User(user_id)
+    }
+    /// frees a user texture (and its bindgroup) during the next `Self::upload_egui_data`.
+    /// don't use the id in the current frame after calling this.
+    pub fn free_user_texture(&mut self, texture_id: TextureId) {
+        if let TextureId::User(_) = texture_id {
+            self.delete_textures.push(texture_id);
+        } else {
+            tracing::warn!(
+                "ignoring free_user_texture call with an egui managed texture {texture_id:?}"
+            );
+        }
+    }
+    /// draws egui with the provided wgsl source instead of `EGUI_SHADER_SRC`. `None` goes back to the default shader.
+    /// the source is checked with `validate_egui_shader_src` and the pipelines are recreated.
+    /// if the source doesn't have `fs_main_srgb_texture`, textures registered with `linear_view` still use the default shader.
+    pub fn set_custom_shader_src(
+        &mut self,
+        dev: &Device,
+        custom_shader_src: Option<String>,
+    ) -> Result<(), String> {
+        if let Some(shader_src) = custom_shader_src.as_deref() {
+            validate_egui_shader_src(shader_src)?;
+            if self.screen_size_push_constants {
+                screen_size_push_constant_shader_src(shader_src)?;
+            }
+        }
+        self.custom_shader_src = custom_shader_src;
+        self.recreate_pipelines(dev);
+        Ok(())
+    }
+    /// if enabled, the screen size is passed to the vertex shader as a push constant instead of the uniform buffer.
+    /// this saves a `Queue::write_buffer` (a staging copy before the render pass) and a `RenderPass::set_bind_group` per frame.
+    /// the savings are small (the write is 16 bytes and the bind group is bound once per frame) and mostly matter for apps
+    /// which draw many egui frames / layers per second, so this is opt-in. profile with the `egui buffer upload` and
+    /// `egui draw call recording` spans or `WgpuBackend::run_frames_headless` to see if it helps your app.
+    /// the device must be created with `Features::PUSH_CONSTANTS` and a `max_push_constant_size` of at least 16 bytes
+    /// (`WgpuConfig::screen_size_push_constants` does that for you). otherwise, this returns an error and nothing changes.
+    /// the custom shader (if any) is converted with `screen_size_push_constant_shader_src`.
+    /// paint callbacks which set their own bind groups or pipelines are unaffected, as they run in the same render pass.
+    pub fn set_screen_size_push_constants(
+        &mut self,
+        dev: &Device,
+        enabled: bool,
+    ) -> Result<(), String> {
+        if enabled == self.screen_size_push_constants {
+            return Ok(());
+        }
+        if enabled {
+            if !dev.features().contains(Features::PUSH_CONSTANTS) {
+                return Err("device doesn't have the PUSH_CONSTANTS feature".to_string());
+            }
+            if dev.limits().max_push_constant_size < 16 {
+                return Err(format!(
+                    "device's max_push_constant_size {} is smaller than 16 bytes",
+                    dev.limits().max_push_constant_size
+                ));
+            }
+            if let Some(shader_src) = self.custom_shader_src.as_deref() {
+                screen_size_push_constant_shader_src(shader_src)?;
+            }
+        }
+        self.screen_size_push_constants = enabled;
+        self.recreate_pipelines(dev);
+        Ok(())
+    }
+    /// the pipelines must match the depth-stencil attachment of the render pass that egui is drawn in.
+    /// if `Some`, egui is drawn without testing or writing depth / stencil, so the attachment is left untouched for paint callbacks.
+    /// returns an error (and nothing changes) if the format is not a depth or depth-stencil format.
+    pub fn set_depth_stencil_format(
+        &mut self,
+        dev: &Device,
+        depth_stencil_format: Option<TextureFormat>,
+    ) -> Result<(), String> {
+        if depth_stencil_format == self.depth_stencil_format {
+            return Ok(());
+        }
+        if let Some(format) = depth_stencil_format {
+            if format.describe().sample_type != TextureSampleType::Depth {
+                return Err(format!("{format:?} is not a depth stencil format"));
+            }
+        }
+        self.depth_stencil_format = depth_stencil_format;
+        self.recreate_pipelines(dev);
+        Ok(())
+    }
+    /// format of the render pass's depth-stencil attachment that the pipelines were created for
+    pub fn depth_stencil_format(&self) -> Option<TextureFormat> {
+        self.depth_stencil_format
+    }
+    /// if enabled, egui's vertices and indices are written directly into host visible (`MAP_WRITE`) buffers instead of being
+    /// copied from a staging buffer by `Queue::write_buffer_with`. on integrated gpus (unified memory), this saves a copy of
+    /// the mesh data every frame. on discrete gpus, the gpu has to read these buffers from system memory, which is usually
+    /// slower than the copy. so, this is opt-in. the savings grow with the vertex count (see `Self::last_frame_stats`).
+    /// profile with the `egui buffer upload` span or `WgpuBackend::run_frames_headless` before enabling it.
+    ///
+    /// wgpu can't keep buffers persistently mapped. so, we keep up to `MAX_MAPPABLE_MESH_BUFFERS` buffers and map each one again
+    /// once the gpu is done with it. if none of them is mapped yet, the frame falls back to the staging copy.
+    /// the draw calls of an upload must be submitted before the next `Self::upload_egui_data` (which maps the buffer again).
+    /// the device needs `Features::MAPPABLE_PRIMARY_BUFFERS`. otherwise, this returns an error and nothing changes.
+    pub fn set_mappable_mesh_buffers(&mut self, dev: &Device, enabled: bool) -> Result<(), String> {
+        if enabled && !dev.features().contains(Features::MAPPABLE_PRIMARY_BUFFERS) {
+            return Err("device doesn't have the MAPPABLE_PRIMARY_BUFFERS feature".to_string());
+        }
+        if !enabled {
+            self.free_mappable_mesh_buffers
+                .lock()
+                .expect("failed to lock free mappable mesh buffers")
+                .clear();
+            self.current_mappable_mesh_buffer = None;
+            self.mappable_mesh_buffers_count = 0;
+        }
+        self.mappable_mesh_buffers = enabled;
+        Ok(())
+    }
+    /// a mapped mesh buffer which can hold at least `vertices` and `indices`. `None` if all of them are still in use.
+    fn take_mappable_mesh_buffer(
+        &mut self,
+        dev: &Device,
+        vertices: usize,
+        indices: usize,
+    ) -> Option<Arc<MappableMeshBuffer>> {
+        let free_mesh_buffer = self
+            .free_mappable_mesh_buffers
+            .lock()
+            .expect("failed to lock free mappable mesh buffers")
+            .pop();
+        match free_mesh_buffer {
+            Some(mesh_buffer)
+                if mesh_buffer.vertices >= vertices && mesh_buffer.indices >= indices =>
+            {
+                return Some(mesh_buffer)
+            }
+            // too small. drop it and create a bigger one
+            Some(_) => self.mappable_mesh_buffers_count -= 1,
+            None => {}
+        }
+        if self.mappable_mesh_buffers_count >= MAX_MAPPABLE_MESH_BUFFERS {
+            debug!(
+                "no mappable mesh buffer is mapped yet. uploading egui meshes with a staging copy"
+            );
+            return None;
+        }
+        self.mappable_mesh_buffers_count += 1;
+        // grow in powers of two, so that a slowly growing ui doesn't create a new buffer every frame
+        let vertices = vertices.next_power_of_two();
+        let indices = indices.next_power_of_two();
+        Some(Arc::new(MappableMeshBuffer {
+            buffer: dev.create_buffer(&BufferDescriptor {
+                label: Some("egui mappable mesh buffer"),
+                size: (indices * 4 + vertices * 20) as u64,
+                usage: BufferUsages::MAP_WRITE | BufferUsages::INDEX | BufferUsages::VERTEX,
+                mapped_at_creation: true,
+            }),
+            vertex_offset: (indices * 4) as u64,
+            vertices,
+            indices,
+        }))
+    }
+    /// maps the mesh buffer of the previous frame again, so that it can be reused once the gpu is done with it
+    fn recall_mappable_mesh_buffer(&mut self) {
+        if let Some(mesh_buffer) = self.current_mappable_mesh_buffer.take() {
+            let free_mesh_buffers = self.free_mappable_mesh_buffers.clone();
+            let mapped_mesh_buffer = mesh_buffer.clone();
+            mesh_buffer.buffer.slice(..).map_async(
+                wgpu::MapMode::Write,
+                move |result| match result {
+                    Ok(_) => free_mesh_buffers
+                        .lock()
+                        .expect("failed to lock free mappable mesh buffers")
+                        .push(mapped_mesh_buffer),
+                    Err(e) => tracing::error!("failed to map egui mesh buffer: {e}"),
+                },
             );
         }
     }
+    /// drops the pipelines of all formats (as the settings they were created with changed) and creates them for the current one
+    fn recreate_pipelines(&mut self, dev: &Device) {
+        let pipelines = self.create_pipelines(dev, self.surface_format);
+        self.pipelines.clear();
+        self.pipelines.insert(self.surface_format, pipelines);
+    }
+    fn create_pipelines(&self, dev: &Device, surface_format: TextureFormat) -> EguiPipelines {
+        let shader_src = self.custom_shader_src.as_deref().unwrap_or(EGUI_SHADER_SRC);
+        let srgb_shader_src = if shader_src
+            .split_whitespace()
+            .collect::<String>()
+            .contains("fnfs_main_srgb_texture(")
+        {
+            shader_src
+        } else {
+            EGUI_SHADER_SRC
+        };
+        if self.screen_size_push_constants {
+            EguiPipelines {
+                pipeline: Self::create_push_constant_render_pipeline(
+                    dev,
+                    surface_format,
+                    &self.texture_bindgroup_layout,
+                    &screen_size_push_constant_shader_src(shader_src)
+                        .expect("shader was checked when push constants were enabled"),
+                    "fs_main",
+                    self.depth_stencil_format,
+                ),
+                srgb_texture_pipeline: Self::create_push_constant_render_pipeline(
+                    dev,
+                    surface_format,
+                    &self.texture_bindgroup_layout,
+                    &screen_size_push_constant_shader_src(srgb_shader_src)
+                        .expect("shader was checked when push constants were enabled"),
+                    "fs_main_srgb_texture",
+                    self.depth_stencil_format,
+                ),
+            }
+        } else {
+            EguiPipelines {
+                pipeline: Self::create_render_pipeline_with_shader(
+                    dev,
+                    surface_format,
+                    &self.screen_size_bindgroup_layout,
+                    &self.texture_bindgroup_layout,
+                    shader_src,
+                    "fs_main",
+                    self.depth_stencil_format,
+                ),
+                srgb_texture_pipeline: Self::create_render_pipeline_with_shader(
+                    dev,
+                    surface_format,
+                    &self.screen_size_bindgroup_layout,
+                    &self.texture_bindgroup_layout,
+                    srgb_shader_src,
+                    "fs_main_srgb_texture",
+                    self.depth_stencil_format,
+                ),
+            }
+        }
+    }
+    /// creates a bindgroup for the texture view with `Self::texture_bindgroup_layout` and the sampler matching the filter.
+    /// used for both egui's textures and user textures, so that rebuilding a bindgroup (eg: on filter change) gives the same result.
+    fn create_texture_bindgroup(
+        &self,
+        dev: &Device,
+        label: &str,
+        view: &TextureView,
+        filter: egui::TextureFilter,
+    ) -> BindGroup {
+        dev.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.texture_bindgroup_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Sampler(match filter {
+                        egui::TextureFilter::Nearest => &self.nearest_sampler,
+                        egui::TextureFilter::Linear => &self.linear_sampler,
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(view),
+                },
+            ],
+        })
+    }
+    fn on_resume(&mut self, dev: &Device, surface_format: TextureFormat) {
+        if !self.pipelines.contains_key(&surface_format) {
+            let pipelines = self.create_pipelines(dev, surface_format);
+            self.pipelines.insert(surface_format, pipelines);
+        }
+        self.surface_format = surface_format;
+    }
+    /// records the texture uploads into `encoder`. the upload chunks are unmapped at the end, so `encoder` can be submitted.
+    /// returns the number of textures that were actually uploaded (created or updated). ignored deltas are not counted.
     fn set_textures(
         &mut self,
         dev: &Device,
         queue: &Queue,
+        encoder: &mut CommandEncoder,
         textures_delta_set: Vec<(TextureId, ImageDelta)>,
-    ) {
+    ) -> usize {
+        if textures_delta_set.is_empty() {
+            return 0;
+        }
+        let mut textures_uploaded = 0;
+        let _upload_span =
+            tracing::debug_span!("egui texture upload", textures = textures_delta_set.len())
+                .entered();
         for (tex_id, delta) in textures_delta_set {
+            let tex_id = match tex_id {
+                egui::TextureId::Managed(tex_id) => tex_id,
+                egui::TextureId::User(tex_id) => {
+                    tracing::warn!("ignoring egui texture delta for user texture {tex_id}. user textures are owned by the app");
+                    continue;
+                }
+            };
             let (pixels, size) = match delta.image {
-                egui::ImageData::Color(_) => todo!(),
+                egui::ImageData::Color(color_image) => {
+                    let pixels: Vec<u8> = color_image
+                        .pixels
+                        .iter()
+                        .flat_map(|c| c.to_array())
+                        .collect();
+                    (pixels, color_image.size)
+                }
                 egui::ImageData::Font(font_image) => {
                     let pixels: Vec<u8> = font_image
                         .srgba_pixels(Some(1.0))
@@ -627,111 +3514,163 @@ impl EguiPainter {
                     (pixels, font_image.size)
                 }
             };
-            match tex_id {
-                egui::TextureId::Managed(tex_id) => {
-                    if let Some(_) = delta.pos {
-                    } else {
-                        let mip_level_count = if tex_id == 0 {
-                            1
-                        } else {
-                            panic!("get mip map count formula")
-                        };
-                        let new_texture = dev.create_texture(&TextureDescriptor {
-                            label: None,
-                            size: Extent3d {
-                                width: size[0] as u32,
-                                height: size[1] as u32,
-                                depth_or_array_layers: 1,
-                            },
-                            mip_level_count,
-                            sample_count: 1,
-                            dimension: TextureDimension::D2,
-                            format: TextureFormat::Rgba8UnormSrgb,
-                            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                        });
-
-                        queue.write_texture(
-                            ImageCopyTexture {
-                                texture: &new_texture,
-                                mip_level: 0,
-                                origin: Origin3d::default(),
-                                aspect: TextureAspect::All,
-                            },
-                            &pixels,
-                            ImageDataLayout {
-                                offset: 0,
-                                bytes_per_row: Some(
-                                    NonZeroU32::new(size[0] as u32 * 4)
-                                        .expect("texture bytes per row is zero"),
-                                ),
-                                rows_per_image: Some(
-                                    NonZeroU32::new(size[1] as u32)
-                                        .expect("texture rows count is zero"),
-                                ),
-                            },
-                            Extent3d {
-                                width: size[0] as u32,
-                                height: size[1] as u32,
-                                depth_or_array_layers: 1,
-                            },
-                        );
-                        let view = new_texture.create_view(&TextureViewDescriptor {
-                            label: None,
-                            format: Some(TextureFormat::Rgba8UnormSrgb),
-                            dimension: Some(TextureViewDimension::D2),
-                            aspect: TextureAspect::All,
-                            base_mip_level: 0,
-                            mip_level_count: None,
-                            base_array_layer: 0,
-                            array_layer_count: None,
-                        });
-                        let bindgroup = dev.create_bind_group(&BindGroupDescriptor {
-                            label: None,
-                            layout: &self.texture_bindgroup_layout,
-                            entries: &[
-                                BindGroupEntry {
-                                    binding: 0,
-                                    resource: BindingResource::Sampler(if tex_id == 0 {
-                                        &self.nearest_sampler
-                                    } else {
-                                        match delta.options.magnification {
-                                            egui::TextureFilter::Nearest => &self.nearest_sampler,
-                                            egui::TextureFilter::Linear => &self.linear_sampler,
-                                        }
-                                    }),
-                                },
-                                BindGroupEntry {
-                                    binding: 1,
-                                    resource: BindingResource::TextureView(&view),
-                                },
-                            ],
-                        });
-                        self.managed_textures.insert(
-                            tex_id,
-                            EguiTexture {
-                                texture: new_texture,
-                                view,
-                                bindgroup,
-                            },
+            let size = [size[0] as u32, size[1] as u32];
+            // font texture always uses nearest sampler
+            let filter = if tex_id == 0 {
+                egui::TextureFilter::Nearest
+            } else {
+                delta.options.magnification
+            };
+            if let Some(pos) = delta.pos {
+                // partial update (eg: new glyphs in the font texture). egui doesn't change options with partial updates
+                let texture = match self.managed_textures.get(tex_id) {
+                    Some(texture) => texture,
+                    None => {
+                        tracing::warn!(
+                            "ignoring partial update of unknown managed texture {tex_id}"
                         );
+                        continue;
                     }
+                };
+                self.texture_upload_belt.upload(
+                    dev,
+                    queue,
+                    encoder,
+                    &texture.texture,
+                    Origin3d {
+                        x: pos[0] as u32,
+                        y: pos[1] as u32,
+                        z: 0,
+                    },
+                    &pixels,
+                    size,
+                );
+                textures_uploaded += 1;
+                continue;
+            }
+            // egui sends a full delta for an existing texture when its pixels or options (eg: filter) changed.
+            // if the size is the same, we upload into the existing texture and only recreate the bindgroup if the filter changed
+            if let Some(texture) = self
+                .managed_textures
+                .get(tex_id)
+                .filter(|texture| texture.size == size)
+            {
+                self.texture_upload_belt.upload(
+                    dev,
+                    queue,
+                    encoder,
+                    &texture.texture,
+                    Origin3d::default(),
+                    &pixels,
+                    size,
+                );
+                textures_uploaded += 1;
+                if texture.filter != filter {
+                    debug!(
+                        "filter of texture {tex_id} changed to {filter:?}. recreating bindgroup"
+                    );
+                    let bindgroup = self.create_texture_bindgroup(
+                        dev,
+                        "egui texture bindgroup",
+                        &texture.view,
+                        filter,
+                    );
+                    let texture = self
+                        .managed_textures
+                        .get_mut(tex_id)
+                        .expect("failed to find managed texture for full update");
+                    texture.bindgroup = bindgroup;
+                    texture.filter = filter;
                 }
-                egui::TextureId::User(_) => todo!(),
+                continue;
             }
+            // we don't generate mipmaps, so every managed texture has a single mip level
+            let new_texture = dev.create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            });
+            self.texture_upload_belt.upload(
+                dev,
+                queue,
+                encoder,
+                &new_texture,
+                Origin3d::default(),
+                &pixels,
+                size,
+            );
+            textures_uploaded += 1;
+            let view = new_texture.create_view(&TextureViewDescriptor {
+                label: None,
+                format: Some(TextureFormat::Rgba8UnormSrgb),
+                dimension: Some(TextureViewDimension::D2),
+                aspect: TextureAspect::All,
+                base_mip_level: 0,
+                mip_level_count: None,
+                base_array_layer: 0,
+                array_layer_count: None,
+            });
+            let bindgroup =
+                self.create_texture_bindgroup(dev, "egui texture bindgroup", &view, filter);
+            // replaces (and drops) the old texture if egui resized it
+            self.managed_textures.insert(
+                tex_id,
+                EguiTexture {
+                    texture: new_texture,
+                    view,
+                    bindgroup,
+                    filter,
+                    size,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    srgb: TextureFormat::Rgba8UnormSrgb.describe().srgb,
+                },
+            );
         }
+        self.texture_upload_belt.finish();
+        textures_uploaded
     }
+    /// uploads egui's textures, vertices and indices and prepares the draw calls for `Self::draw_egui_with_renderpass`.
+    /// `screen_size_physical` is the size of the attachment that egui will be drawn into. used for the scissor rects.
+    /// texture uploads are recorded as copies into `encoder`. record the egui render pass into the same encoder (or a later one)
+    /// and submit it before the next `Self::upload_egui_data`.
+    /// paint callbacks' `prepare` functions are called here.
     pub fn upload_egui_data(
         &mut self,
         dev: &Device,
         queue: &Queue,
+        encoder: &mut CommandEncoder,
         EguiGfxData {
             meshes,
             textures_delta,
             screen_size_logical,
+            viewport,
         }: EguiGfxData,
         screen_size_physical: [u32; 2],
     ) {
-        let scale = screen_size_physical[0] as f32 / screen_size_logical[0];
+        // the previous frame's draw calls and texture uploads were submitted by now
+        self.recall_mappable_mesh_buffer();
+        self.texture_upload_belt.recall();
+        let [viewport_x, viewport_y, viewport_width, viewport_height] = viewport.unwrap_or([
+            0.0,
+            0.0,
+            screen_size_physical[0] as f32,
+            screen_size_physical[1] as f32,
+        ]);
+        self.viewport = Some([viewport_x, viewport_y, viewport_width, viewport_height]);
+        // scale per axis. the physical size might not be an exact multiple of the logical size on both axes
+        // (eg: when rendering to a target whose aspect ratio is different from the window's)
+        let scale = [
+            viewport_width / screen_size_logical[0],
+            viewport_height / screen_size_logical[1],
+        ];
         self.draw_calls.clear();
         // first deal with textures
         {
@@ -739,25 +3678,39 @@ impl EguiPainter {
             // so we store them in self.delete_textures.
             // otoh, the textures that were scheduled to be deleted previous frame, we will delete now
 
-            let delete_textures = std::mem::replace(&mut self.delete_textures, textures_delta.free);
+            self.last_frame_stats = FrameStats {
+                textures_freed: textures_delta.free.len(),
+                ..Default::default()
+            };
+            let mut delete_textures =
+                std::mem::replace(&mut self.delete_textures, textures_delta.free);
+            delete_textures.append(
+                &mut self
+                    .dropped_user_textures
+                    .lock()
+                    .expect("failed to lock dropped user textures"),
+            );
             // remove textures to be deleted in previous frame
             for tid in delete_textures {
                 match tid {
                     TextureId::Managed(key) => {
                         self.managed_textures.remove(key);
                     }
-                    TextureId::User(_) => todo!(),
+                    TextureId::User(key) => {
+                        self.user_textures.remove(key);
+                    }
                 }
             }
             // upload textures
-            self.set_textures(dev, queue, textures_delta.set);
+            self.last_frame_stats.textures_uploaded =
+                self.set_textures(dev, queue, encoder, textures_delta.set);
+        }
+        // update screen size uniform buffer. we write all 16 bytes (see `Self::screen_size_buffer`), so the padding is never stale
+        self.screen_size = [screen_size_logical[0], screen_size_logical[1], 0.0, 0.0];
+        // with push constants, the screen size is recorded into the render pass instead
+        if !self.screen_size_push_constants {
+            queue.write_buffer(&self.screen_size_buffer, 0, cast_slice(&self.screen_size));
         }
-        // update screen size uniform buffer
-        queue.write_buffer(
-            &self.screen_size_buffer,
-            0,
-            cast_slice(&screen_size_logical),
-        );
 
         {
             // total vertices and indices lengths
@@ -771,46 +3724,70 @@ impl EguiPainter {
             if vb_len == 0 {
                 return;
             }
-            // resize if vertex or index buffer capcities are not enough
-            if self.vb_len < vb_len {
-                self.vb = dev.create_buffer(&BufferDescriptor {
-                    label: Some("egui vertex buffer"),
-                    size: vb_len as u64 * 20,
-                    usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
-                    mapped_at_creation: false,
-                });
-                self.vb_len = vb_len;
+            self.last_frame_stats.vertices = vb_len;
+            self.last_frame_stats.indices = ib_len;
+            let _buffer_upload_span = tracing::debug_span!(
+                "egui buffer upload",
+                meshes = meshes.len(),
+                vertices = vb_len,
+                indices = ib_len
+            )
+            .entered();
+            let mappable_mesh_buffer = if self.mappable_mesh_buffers {
+                self.take_mappable_mesh_buffer(dev, vb_len, ib_len)
+            } else {
+                None
+            };
+            if mappable_mesh_buffer.is_none() {
+                // resize if vertex or index buffer capcities are not enough
+                self.preallocate(dev, vb_len, ib_len);
             }
-            if self.ib_len < ib_len {
-                self.ib = dev.create_buffer(&BufferDescriptor {
-                    label: Some("egui index buffer"),
-                    size: ib_len as u64 * 4,
-                    usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
-                    mapped_at_creation: false,
-                });
-                self.ib_len = ib_len;
-            }
-            // create mutable slices for vertex and index buffers
-            let mut vertex_buffer_mut = queue.write_buffer_with(
-                &self.vb,
-                0,
-                NonZeroU64::new(
-                    (self.vb_len * 20)
-                        .try_into()
-                        .expect("unreachable as usize is u64"),
-                )
-                .expect("vertex buffer length should not be zero"),
-            );
-            let mut index_buffer_mut = queue.write_buffer_with(
-                &self.ib,
-                0,
-                NonZeroU64::new(
-                    (self.ib_len * 4)
-                        .try_into()
-                        .expect("unreachable as usize is u64"),
-                )
-                .expect("index buffer length should not be zero"),
-            );
+            // create mutable slices for vertex and index buffers. either the mapped ranges of the mappable mesh buffer
+            // or staging buffers which are copied into `Self::vb` / `Self::ib` at the next submission
+            type BufferMut<'a> = Box<dyn std::ops::DerefMut<Target = [u8]> + 'a>;
+            let (mut vertex_buffer_mut, mut index_buffer_mut): (BufferMut, BufferMut) =
+                match mappable_mesh_buffer.as_deref() {
+                    Some(mesh_buffer) => (
+                        Box::new(
+                            mesh_buffer
+                                .buffer
+                                .slice(mesh_buffer.vertex_offset..)
+                                .get_mapped_range_mut(),
+                        ),
+                        Box::new(
+                            mesh_buffer
+                                .buffer
+                                .slice(..mesh_buffer.vertex_offset)
+                                .get_mapped_range_mut(),
+                        ),
+                    ),
+                    None => (
+                        Box::new(
+                            queue.write_buffer_with(
+                                &self.vb,
+                                0,
+                                NonZeroU64::new(
+                                    (self.vb_len * 20)
+                                        .try_into()
+                                        .expect("unreachable as usize is u64"),
+                                )
+                                .expect("vertex buffer length should not be zero"),
+                            ),
+                        ),
+                        Box::new(
+                            queue.write_buffer_with(
+                                &self.ib,
+                                0,
+                                NonZeroU64::new(
+                                    (self.ib_len * 4)
+                                        .try_into()
+                                        .expect("unreachable as usize is u64"),
+                                )
+                                .expect("index buffer length should not be zero"),
+                            ),
+                        ),
+                    ),
+                };
             // offsets from where to start writing vertex or index buffer data
             let mut vb_offset = 0;
             let mut ib_offset = 0;
@@ -819,35 +3796,16 @@ impl EguiPainter {
                     clip_rect,
                     primitive,
                 } = clipped_primitive;
-                // copy paste from official egui impl because i have no idea what this is :D
-                let clip_min_x = scale * clip_rect.min.x;
-                let clip_min_y = scale * clip_rect.min.y;
-                let clip_max_x = scale * clip_rect.max.x;
-                let clip_max_y = scale * clip_rect.max.y;
-                let clip_min_x = clip_min_x.clamp(0.0, screen_size_physical[0] as f32);
-                let clip_min_y = clip_min_y.clamp(0.0, screen_size_physical[1] as f32);
-                let clip_max_x = clip_max_x.clamp(clip_min_x, screen_size_physical[0] as f32);
-                let clip_max_y = clip_max_y.clamp(clip_min_y, screen_size_physical[1] as f32);
-
-                let clip_min_x = clip_min_x.round() as u32;
-                let clip_min_y = clip_min_y.round() as u32;
-                let clip_max_x = clip_max_x.round() as u32;
-                let clip_max_y = clip_max_y.round() as u32;
-
-                let width = (clip_max_x - clip_min_x).max(1);
-                let height = (clip_max_y - clip_min_y).max(1);
-
-                // Clip scissor rectangle to target size.
-                let clip_x = clip_min_x.min(screen_size_physical[0]);
-                let clip_y = clip_min_y.min(screen_size_physical[1]);
-                let clip_width = width.min(screen_size_physical[0] - clip_x);
-                let clip_height = height.min(screen_size_physical[1] - clip_y);
-
                 // Skip rendering with zero-sized clip areas.
-                if clip_width == 0 || clip_height == 0 {
-                    continue;
-                }
-                let scissor_rect = [clip_x, clip_y, clip_width, clip_height];
+                let scissor_rect = match clip_rect_to_scissor_rect(
+                    clip_rect,
+                    [viewport_x, viewport_y, viewport_width, viewport_height],
+                    scale,
+                    screen_size_physical,
+                ) {
+                    Some(scissor_rect) => scissor_rect,
+                    None => continue,
+                };
                 match primitive {
                     egui::epaint::Primitive::Mesh(mesh) => {
                         let Mesh {
@@ -894,17 +3852,68 @@ impl EguiPainter {
                                     screen_size_logical.into(),
                                 ),
                                 clip_rect,
-                                pixels_per_point: scale,
+                                // egui only supports uniform scale
+                                pixels_per_point: scale[0],
                                 screen_size_px: screen_size_physical,
                             },
                         });
                     }
                 }
             }
+            self.last_frame_stats.draw_calls = self.draw_calls.len();
+            // the mapped ranges must be dropped before unmapping
+            drop(vertex_buffer_mut);
+            drop(index_buffer_mut);
+            if let Some(mesh_buffer) = mappable_mesh_buffer {
+                mesh_buffer.buffer.unmap();
+                self.current_mappable_mesh_buffer = Some(mesh_buffer);
+            }
         }
     }
 }
 
+/// converts an egui clip rect (logical pixels) into a scissor rect `[x, y, width, height]` in physical pixels of the render target.
+/// `viewport` is `[x, y, width, height]` in physical pixels (the letterboxed virtual screen or the full target) and
+/// `scale` is physical pixels per logical pixel for each axis. the result is clamped to the viewport (so that we don't draw
+/// into the letterbox bars) and to `target_size`. returns `None` if the clamped rect is empty.
+fn clip_rect_to_scissor_rect(
+    clip_rect: Rect,
+    viewport: [f32; 4],
+    scale: [f32; 2],
+    target_size: [u32; 2],
+) -> Option<[u32; 4]> {
+    let [viewport_x, viewport_y, viewport_width, viewport_height] = viewport;
+    // copy paste from official egui impl because i have no idea what this is :D
+    let clip_min_x = viewport_x + scale[0] * clip_rect.min.x;
+    let clip_min_y = viewport_y + scale[1] * clip_rect.min.y;
+    let clip_max_x = viewport_x + scale[0] * clip_rect.max.x;
+    let clip_max_y = viewport_y + scale[1] * clip_rect.max.y;
+    // don't draw into the letterbox bars
+    let clip_min_x = clip_min_x.clamp(viewport_x, viewport_x + viewport_width);
+    let clip_min_y = clip_min_y.clamp(viewport_y, viewport_y + viewport_height);
+    let clip_max_x = clip_max_x.clamp(clip_min_x, viewport_x + viewport_width);
+    let clip_max_y = clip_max_y.clamp(clip_min_y, viewport_y + viewport_height);
+
+    let clip_min_x = clip_min_x.round() as u32;
+    let clip_min_y = clip_min_y.round() as u32;
+    let clip_max_x = clip_max_x.round() as u32;
+    let clip_max_y = clip_max_y.round() as u32;
+
+    let width = (clip_max_x - clip_min_x).max(1);
+    let height = (clip_max_y - clip_min_y).max(1);
+
+    // Clip scissor rectangle to target size.
+    let clip_x = clip_min_x.min(target_size[0]);
+    let clip_y = clip_min_y.min(target_size[1]);
+    let clip_width = width.min(target_size[0] - clip_x);
+    let clip_height = height.min(target_size[1] - clip_y);
+
+    if clip_width == 0 || clip_height == 0 {
+        return None;
+    }
+    Some([clip_x, clip_y, clip_width, clip_height])
+}
+
 pub const SCREEN_SIZE_UNIFORM_BUFFER_BINDGROUP_ENTRY: [BindGroupLayoutEntry; 1] =
     [BindGroupLayoutEntry {
         binding: 0,
@@ -984,6 +3993,43 @@ pub const EGUI_PIPELINE_BLEND_STATE: BlendState = BlendState {
     },
 };
 
+/// egui doesn't test or write depth / stencil, so the attachment is left untouched for paint callbacks
+pub fn egui_pipeline_depth_stencil_state(format: TextureFormat) -> DepthStencilState {
+    DepthStencilState {
+        format,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState {
+            front: StencilFaceState::IGNORE,
+            back: StencilFaceState::IGNORE,
+            read_mask: 0,
+            write_mask: 0,
+        },
+        bias: DepthBiasState::default(),
+    }
+}
+/// clears depth to 1.0 (far) and stencil (if the format has a stencil aspect) to 0 at the start of the egui render pass
+fn depth_stencil_attachment(
+    view: &TextureView,
+    format: TextureFormat,
+) -> RenderPassDepthStencilAttachment {
+    let has_stencil = matches!(
+        format,
+        TextureFormat::Depth24PlusStencil8 | TextureFormat::Depth32FloatStencil8
+    );
+    RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: Some(Operations {
+            load: LoadOp::Clear(1.0),
+            store: true,
+        }),
+        stencil_ops: has_stencil.then_some(Operations {
+            load: LoadOp::Clear(0),
+            store: true,
+        }),
+    }
+}
+
 // `Default::default` is not const. so, we have to manually fill the default values
 
 pub const EGUI_LINEAR_SAMPLER_DESCRIPTOR: SamplerDescriptor = SamplerDescriptor {
@@ -1015,3 +4061,358 @@ pub const EGUI_NEAREST_SAMPLER_DESCRIPTOR: SamplerDescriptor = SamplerDescriptor
     anisotropy_clamp: None,
     border_color: None,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logical_rect(min: [f32; 2], max: [f32; 2]) -> Rect {
+        Rect::from_min_max(min.into(), max.into())
+    }
+
+    #[test]
+    fn scissor_rect_uses_per_axis_scale() {
+        // a 100x100 logical screen drawn into a 200x150 target
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([10.0, 20.0], [50.0, 60.0]),
+                [0.0, 0.0, 200.0, 150.0],
+                [2.0, 1.5],
+                [200, 150]
+            ),
+            Some([20, 30, 80, 60])
+        );
+        // the full screen must cover the full target on both axes
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([0.0, 0.0], [100.0, 100.0]),
+                [0.0, 0.0, 200.0, 150.0],
+                [2.0, 1.5],
+                [200, 150]
+            ),
+            Some([0, 0, 200, 150])
+        );
+    }
+
+    #[test]
+    fn scissor_rect_rounds_to_nearest_pixel() {
+        // 0.45 -> 0, 15.3 -> 15 and 1.5 * 3.1 = 4.65 -> 5
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([0.3, 3.1], [10.2, 10.2]),
+                [0.0, 0.0, 100.0, 100.0],
+                [1.5, 1.5],
+                [100, 100]
+            ),
+            Some([0, 5, 15, 10])
+        );
+    }
+
+    #[test]
+    fn scissor_rect_is_clamped_to_target() {
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([-20.0, 50.0], [80.0, 200.0]),
+                [0.0, 0.0, 200.0, 150.0],
+                [2.0, 1.5],
+                [200, 150]
+            ),
+            Some([0, 75, 160, 75])
+        );
+        // completely to the right of the target
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([150.0, 0.0], [180.0, 10.0]),
+                [0.0, 0.0, 200.0, 150.0],
+                [2.0, 1.5],
+                [200, 150]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn scissor_rect_stays_inside_letterbox_viewport() {
+        // 100x100 virtual screen scaled by 2 and centered in a 300x200 target with 50px bars on the left and right
+        let viewport = [50.0, 0.0, 200.0, 200.0];
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([10.0, 10.0], [20.0, 30.0]),
+                viewport,
+                [2.0, 2.0],
+                [300, 200]
+            ),
+            Some([70, 20, 20, 40])
+        );
+        // a clip rect bigger than the virtual screen must not reach into the bars
+        assert_eq!(
+            clip_rect_to_scissor_rect(
+                logical_rect([-50.0, -50.0], [500.0, 500.0]),
+                viewport,
+                [2.0, 2.0],
+                [300, 200]
+            ),
+            Some([50, 0, 200, 200])
+        );
+    }
+
+    #[test]
+    fn validate_egui_shader_src_ignores_whitespace() {
+        assert_eq!(validate_egui_shader_src(EGUI_SHADER_SRC), Ok(()));
+        let reformatted = EGUI_SHADER_SRC
+            .replace("@group(0) @binding(0)", "@group(0)\n    @binding(0)")
+            .replace("fn vs_main(", "fn vs_main (");
+        assert_eq!(validate_egui_shader_src(&reformatted), Ok(()));
+    }
+
+    #[test]
+    fn validate_egui_shader_src_names_the_missing_item() {
+        // `fs_main_srgb_texture` is optional and must not count as `fs_main`
+        let without_fs_main = EGUI_SHADER_SRC.replace("fn fs_main(", "fn fs_custom(");
+        assert_eq!(
+            validate_egui_shader_src(&without_fs_main),
+            Err("shader is missing the fragment entry point `fs_main`".to_string())
+        );
+        let moved_texture =
+            EGUI_SHADER_SRC.replace("@group(1) @binding(1)", "@group(2) @binding(1)");
+        assert_eq!(
+            validate_egui_shader_src(&moved_texture),
+            Err("shader is missing the texture binding `@group(1) @binding(1)`".to_string())
+        );
+    }
+
+    #[test]
+    fn push_constant_shader_src_moves_texture_bindings_to_group_0() {
+        let src = screen_size_push_constant_shader_src(EGUI_SHADER_SRC)
+            .expect("default shader must be convertible");
+        assert!(src.contains("var<push_constant> u_screen_size: vec4<f32>;"));
+        assert!(!src.contains("var<uniform>"));
+        assert!(!src.contains("@group(1)"));
+        assert!(src.contains("@group(0) @binding(0) var r_tex_sampler: sampler;"));
+        assert!(src.contains("@group(0) @binding(1) var r_tex_color: texture_2d<f32>;"));
+        // a shader without the uniform can't be converted
+        assert!(screen_size_push_constant_shader_src("fn vs_main() {}").is_err());
+    }
+
+    #[test]
+    fn premultiply_rgba_keeps_opaque_and_clears_transparent_pixels() {
+        // alpha 255 must leave the color untouched (no rounding drift) and alpha 0 must zero the color
+        assert_eq!(
+            premultiply_rgba(&[255, 128, 3, 255, 200, 100, 50, 0]),
+            vec![255, 128, 3, 255, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn premultiply_rgba_multiplies_in_linear_space_and_rounds() {
+        // the expected values come from egui's srgb <-> linear conversion and round to nearest.
+        // a plain `c * a / 255` in srgb space would give 128, 50 and 1 instead, which looks too dark
+        assert_eq!(
+            premultiply_rgba(&[255, 255, 255, 128, 200, 0, 0, 64, 255, 255, 255, 1]),
+            vec![188, 188, 188, 128, 106, 0, 0, 64, 13, 13, 13, 1]
+        );
+    }
+
+    #[test]
+    fn premultiply_rgba_ignores_a_trailing_partial_pixel() {
+        assert_eq!(premultiply_rgba(&[1, 2, 3, 255, 4, 5]), vec![1, 2, 3, 255]);
+        assert!(premultiply_rgba(&[]).is_empty());
+    }
+
+    /// a device for the tests which need a gpu. `None` if there's no adapter (eg: ci without a gpu), so those tests are skipped.
+    fn test_device() -> Option<(Device, Queue)> {
+        let instance = Instance::new(Backends::all());
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))?;
+        pollster::block_on(adapter.request_device(&DeviceDescriptor::default(), None)).ok()
+    }
+
+    fn texture_options(filter: egui::TextureFilter) -> egui::TextureOptions {
+        egui::TextureOptions {
+            magnification: filter,
+            minification: filter,
+        }
+    }
+
+    fn textures_only_frame(set: Vec<(TextureId, ImageDelta)>) -> EguiGfxData {
+        let mut textures_delta = egui::TexturesDelta::default();
+        textures_delta.set = set;
+        EguiGfxData {
+            meshes: Vec::new(),
+            textures_delta,
+            screen_size_logical: [100.0, 100.0],
+            viewport: None,
+        }
+    }
+
+    /// uploads like a frame would: the texture copies go into the frame's encoder, which is submitted afterwards
+    fn upload_and_submit(
+        painter: &mut EguiPainter,
+        dev: &Device,
+        queue: &Queue,
+        egui_gfx_data: EguiGfxData,
+        size: [u32; 2],
+    ) {
+        let mut encoder = dev.create_command_encoder(&CommandEncoderDescriptor::default());
+        painter.upload_egui_data(dev, queue, &mut encoder, egui_gfx_data, size);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    #[test]
+    fn managed_texture_updates_switch_filter_and_reuse_the_texture() {
+        let (dev, queue) = match test_device() {
+            Some(device) => device,
+            None => {
+                eprintln!("no gpu adapter available. skipping");
+                return;
+            }
+        };
+        let mut painter = EguiPainter::new(&dev, TextureFormat::Bgra8UnormSrgb);
+        let id = TextureId::Managed(1);
+        let image = egui::ColorImage::new([4, 4], egui::Color32::RED);
+        upload_and_submit(
+            &mut painter,
+            &dev,
+            &queue,
+            textures_only_frame(vec![
+                (
+                    id,
+                    ImageDelta::full(image.clone(), texture_options(egui::TextureFilter::Linear)),
+                ),
+                // user textures are owned by the app. so, this delta is ignored and not counted as an upload
+                (
+                    TextureId::User(7),
+                    ImageDelta::full(image.clone(), texture_options(egui::TextureFilter::Linear)),
+                ),
+            ]),
+            [100, 100],
+        );
+        assert_eq!(painter.last_frame_stats().textures_uploaded, 1);
+        let texture = painter
+            .managed_textures
+            .get(1)
+            .expect("texture must be created");
+        assert_eq!(texture.filter, egui::TextureFilter::Linear);
+        assert_eq!(texture.size, [4, 4]);
+
+        // egui resends the whole image when only the options change
+        upload_and_submit(
+            &mut painter,
+            &dev,
+            &queue,
+            textures_only_frame(vec![(
+                id,
+                ImageDelta::full(image, texture_options(egui::TextureFilter::Nearest)),
+            )]),
+            [100, 100],
+        );
+        assert_eq!(
+            painter
+                .managed_textures
+                .get(1)
+                .expect("texture must still exist")
+                .filter,
+            egui::TextureFilter::Nearest
+        );
+
+        // partial updates are written into the existing texture
+        upload_and_submit(
+            &mut painter,
+            &dev,
+            &queue,
+            textures_only_frame(vec![(
+                id,
+                ImageDelta::partial(
+                    [1, 1],
+                    egui::ColorImage::new([2, 2], egui::Color32::BLUE),
+                    texture_options(egui::TextureFilter::Nearest),
+                ),
+            )]),
+            [100, 100],
+        );
+        assert_eq!(
+            painter
+                .managed_textures
+                .get(1)
+                .expect("texture must still exist")
+                .size,
+            [4, 4]
+        );
+
+        // a different size replaces the texture
+        upload_and_submit(
+            &mut painter,
+            &dev,
+            &queue,
+            textures_only_frame(vec![(
+                id,
+                ImageDelta::full(
+                    egui::ColorImage::new([8, 2], egui::Color32::GREEN),
+                    texture_options(egui::TextureFilter::Nearest),
+                ),
+            )]),
+            [100, 100],
+        );
+        assert_eq!(
+            painter
+                .managed_textures
+                .get(1)
+                .expect("texture must be recreated")
+                .size,
+            [8, 2]
+        );
+        dev.poll(Maintain::Wait);
+    }
+
+    fn colored_rect(clip_rect: Rect) -> ClippedPrimitive {
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(clip_rect, egui::Color32::WHITE);
+        ClippedPrimitive {
+            clip_rect,
+            primitive: egui::epaint::Primitive::Mesh(mesh),
+        }
+    }
+
+    /// an offscreen target (eg: `WgpuBackend::render_to_target`) is usually not the size of the window. the scissor rects
+    /// must be computed and clamped in the target's space, not the window's.
+    #[test]
+    fn offscreen_target_scissor_rects_use_the_target_size() {
+        let (dev, queue) = match test_device() {
+            Some(device) => device,
+            None => {
+                eprintln!("no gpu adapter available. skipping");
+                return;
+            }
+        };
+        let mut painter = EguiPainter::new(&dev, TextureFormat::Rgba8UnormSrgb);
+        // a 100x100 point ui (eg: from a 800x800 window) drawn into a 256x128 target
+        upload_and_submit(
+            &mut painter,
+            &dev,
+            &queue,
+            EguiGfxData {
+                meshes: vec![
+                    colored_rect(logical_rect([0.0, 0.0], [100.0, 100.0])),
+                    colored_rect(logical_rect([50.0, 50.0], [150.0, 150.0])),
+                ],
+                textures_delta: Default::default(),
+                screen_size_logical: [100.0, 100.0],
+                viewport: None,
+            },
+            [256, 128],
+        );
+        let scissor_rects: Vec<[u32; 4]> = painter
+            .draw_calls()
+            .iter()
+            .map(|draw_call| match draw_call {
+                EguiDrawCalls::Mesh { clip_rect, .. } => *clip_rect,
+                EguiDrawCalls::Callback { clip_rect, .. } => *clip_rect,
+            })
+            .collect();
+        assert_eq!(scissor_rects, vec![[0, 0, 256, 128], [128, 64, 128, 64]]);
+        dev.poll(Maintain::Wait);
+    }
+}
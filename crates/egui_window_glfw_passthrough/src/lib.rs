@@ -9,6 +9,7 @@ use glfw::StandardCursor;
 use glfw::WindowEvent;
 use glfw::WindowHint;
 use raw_window_handle::*;
+use std::collections::HashSet;
 use std::sync::mpsc::Receiver;
 
 pub struct GlfwBackend {
@@ -16,13 +17,79 @@ pub struct GlfwBackend {
     pub events_receiver: Receiver<(f64, WindowEvent)>,
     pub window: glfw::Window,
     pub size_physical_pixels: [u32; 2],
+    /// content scale of the window. this is `[scale_override; 2]` if the override is set.
     pub scale: [f32; 2],
+    /// set with `WindowBackend::set_scale_factor_override`
+    pub scale_override: Option<f32>,
+    /// set with `WindowBackend::set_virtual_screen_size`
+    pub virtual_screen_size: Option<[f32; 2]>,
     pub cursor_pos_physical_pixels: [f32; 2],
+    /// glfw's cursor position in screen coordinates at the end of the previous tick. used for the raw mouse delta
+    pub cursor_pos_screen_coordinates: [f64; 2],
+    /// raw mouse motion accumulated since the last `WindowBackend::take_raw_mouse_delta`.
+    /// only accumulated while the cursor is disabled (grabbed) and raw mouse motion is enabled (see `WindowBackend::take_raw_mouse_delta`).
+    pub raw_mouse_delta: [f64; 2],
+    /// see `WindowBackend::frame_mouse_delta`. updated in `WindowBackend::take_raw_input`
+    pub frame_mouse_delta: [f64; 2],
+    /// relative mouse motion since the last `WindowBackend::take_raw_input`. becomes `Self::frame_mouse_delta`
+    pub pending_frame_mouse_delta: [f64; 2],
     pub raw_input: RawInput,
+    /// time of the previous `WindowBackend::take_raw_input`. used to calculate `RawInput::predicted_dt`
+    pub last_frame_time: Option<f64>,
+    /// keys which are currently held down. used to release them when the window loses focus
+    pub pressed_keys: HashSet<Key>,
+    /// taken from `GlfwConfig::enable_gamepad`
+    pub enable_gamepad: bool,
+    /// gamepad buttons (from `GAMEPAD_BUTTON_TO_KEY`) held down during the last tick. used to only push events on transitions
+    pub gamepad_buttons_pressed: HashSet<glfw::GamepadButton>,
     pub cursor_icon: glfw::StandardCursor,
+    /// `egui::Context::wants_pointer_input` after the most recent `UserAppData::run`.
+    /// useful for overlays to enable mouse passthrough when egui doesn't need the pointer.
+    /// `window.set_mouse_passthrough(!backend.egui_wants_pointer_input)`
+    ///
+    /// NOTE: this is one frame late. inside `UserAppData::run`, this still has the value from the previous frame.
+    /// and the clicks that arrive before the next frame will be handled based on the passthrough state set by the previous frame.
+    /// so, a fast click right after the cursor moves on to / away from a widget might go to the wrong window.
+    pub egui_wants_pointer_input: bool,
+    /// `egui::Context::wants_keyboard_input` after the most recent `UserAppData::run`.
+    /// same one-frame-latency caveat as `Self::egui_wants_pointer_input`
+    pub egui_wants_keyboard_input: bool,
+    /// true while an egui text field has focus (`PlatformOutput::text_cursor_pos` is `Some`). unlike
+    /// `Self::egui_wants_keyboard_input`, this is false when a button or other widget has keyboard focus.
+    /// same one-frame-latency caveat as `Self::egui_wants_pointer_input`. see `GlfwConfig::soft_keyboard_callback`
+    pub egui_wants_text_input: bool,
+    /// taken from `GlfwConfig::soft_keyboard_callback`
+    pub soft_keyboard_callback: Option<Box<dyn FnMut(bool)>>,
     pub frame_events: Vec<WindowEvent>,
     pub resized_event_pending: bool,
+    /// set by `Self::tick` when the user asks to close the window. `Self::pump_frame` takes it and asks
+    /// `UserAppData::on_close_requested` whether to actually close.
+    pub close_requested: bool,
+    /// true while the window is minimized (iconified). we don't render while minimized as the framebuffer size is zero.
+    pub minimized: bool,
+    /// set with `WindowBackend::set_min_inner_size`. in logical pixels
+    pub min_inner_size: Option<[f32; 2]>,
+    /// set with `WindowBackend::set_max_inner_size`. in logical pixels
+    pub max_inner_size: Option<[f32; 2]>,
+    /// taken from `GlfwConfig::render_only_if_dirty`. can be changed at runtime.
+    pub render_only_if_dirty: bool,
+    /// used with `Self::render_only_if_dirty`
+    pub frame_dirty_tracker: FrameDirtyTracker,
+    /// taken from `GlfwConfig::coalesce_pointer_moves`. can be changed at runtime.
+    pub coalesce_pointer_moves: bool,
+    /// taken from `GlfwConfig::auto_suspend_after`. can be changed at runtime.
+    pub auto_suspend_after: Option<std::time::Duration>,
+    /// glfw time of the most recent input event. used with `Self::auto_suspend_after`
+    pub last_input_time: f64,
+    /// true while the gfx backend is suspended due to `Self::auto_suspend_after`
+    pub auto_suspended: bool,
     pub backend_config: BackendConfig,
+    /// taken from `GlfwConfig::open_url_callback`
+    pub open_url_callback: Option<Box<dyn FnMut(&egui::output::OpenUrl)>>,
+    /// taken from `GlfwConfig::key_map_override`
+    pub key_map_override: Option<Box<dyn Fn(glfw::Key, glfw::Scancode) -> Option<Key>>>,
+    /// taken from `GlfwConfig::clipboard_handler`. used instead of glfw's clipboard if set
+    pub clipboard_handler: Option<Box<dyn ClipboardHandler>>,
 }
 
 unsafe impl HasRawWindowHandle for GlfwBackend {
@@ -36,15 +103,114 @@ unsafe impl HasRawDisplayHandle for GlfwBackend {
     }
 }
 
+/// A handle to wake up glfw's event loop from any thread. get it with `GlfwBackend::wake_handle`.
+///
+/// `glfw::Glfw` is not `Send`, but `glfwPostEmptyEvent` is thread safe. so, we call it directly.
+/// this unblocks `Glfw::wait_events` / `Glfw::wait_events_timeout`. our own event loop polls every frame at the moment,
+/// so this only matters if you drive the backend yourself with one of the wait functions
+/// (eg: waiting for egui's `repaint_after` and using this handle to redraw when a background task finishes).
+#[derive(Debug, Clone, Copy)]
+pub struct GlfwWakeHandle {
+    // only `GlfwBackend` can create this, to make sure that glfw is initialized
+    _private: (),
+}
+impl GlfwWakeHandle {
+    /// posts an empty event to glfw's event queue, which wakes up the thread waiting for events.
+    pub fn wake(&self) {
+        unsafe {
+            glfw::ffi::glfwPostEmptyEvent();
+        }
+    }
+}
+
+/// initial mode of the window. glfw's own `WindowMode` borrows a monitor, so we can't store it in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GlfwWindowMode {
+    #[default]
+    Windowed,
+    /// fullscreen on the primary monitor. falls back to windowed if there's no primary monitor
+    FullscreenPrimaryMonitor,
+}
+
 /// The configuration struct for Glfw Backend
 ///
-#[derive(Default)]
 pub struct GlfwConfig {
+    /// initial width of the window in screen coordinates. default is 800
+    pub width: u32,
+    /// initial height of the window in screen coordinates. default is 600
+    pub height: u32,
+    /// window title. default is "Overlay Window"
+    pub title: String,
+    /// default is windowed
+    pub window_mode: GlfwWindowMode,
+    /// poll the connected gamepads every tick and translate the D-pad / face buttons into egui key events
+    /// (see `GAMEPAD_BUTTON_TO_KEY`) for navigation in media centers, game launchers etc..
+    /// disabled by default, as polling every frame has a small cost.
+    pub enable_gamepad: bool,
+    /// whether the window has decorations (title bar, borders). default is true.
+    /// can be changed later with `WindowBackend::set_decorations`
+    pub decorations: bool,
+    /// whether the user can resize the window. default is true.
+    /// can be changed later with `WindowBackend::set_resizable`
+    pub resizable: bool,
+    /// skip the frames whose egui output is identical to the previous frame (see `FrameDirtyTracker`). default is false.
+    /// the frame is still prepared, but `GfxBackend::discard_frame` is called instead of rendering and presenting it,
+    /// which releases the swapchain image without showing it. so, the previous frame stays on screen.
+    /// gfx backends which can't discard frames (eg: glow on web) still render them.
+    /// only the egui output is tracked. if you draw anything else in `UserAppData::run`, keep this disabled.
+    pub render_only_if_dirty: bool,
+    /// if set, `GfxBackend::suspend` is called after there's been no input for this long, which releases the surface
+    /// (and other gpu resources of the gfx backend). the next input calls `GfxBackend::resume` before egui sees it.
+    /// no frames are drawn while suspended, so the window shows its last frame (or nothing, depending on the platform).
+    /// useful for overlays which are only interacted with occasionally. wgpu releases its surface,
+    /// while glow and three-d keep their resources and only stop drawing. the gfx backend must implement suspend.
+    /// default is `None`
+    pub auto_suspend_after: Option<std::time::Duration>,
+    /// if the pointer moves several times between two other events (eg: a 1000hz mouse), only keep the last position
+    /// instead of pushing every `Event::PointerMoved` into egui's input. moves around button presses are kept, so clicks
+    /// still happen at the right position. disable it if you need the full motion path (eg: a drawing app). default is true.
+    pub coalesce_pointer_moves: bool,
     /// This callback is called with `&mut Glfw` just before creating a window
     pub glfw_callback: Option<Box<dyn FnOnce(&mut Glfw)>>,
     /// This will be called right after window creation. you can use this to do things at startup like
     /// resizing, changing title, changing to fullscreen etc..
     pub window_callback: Option<Box<dyn FnOnce(&mut glfw::Window)>>,
+    /// egui sets `PlatformOutput::open_url` when a hyperlink is clicked. this callback will be called with that url.
+    /// you can open it with a crate like `webbrowser` or just intercept it.
+    /// if this is `None`, urls are ignored (with a warning).
+    pub open_url_callback: Option<Box<dyn FnMut(&egui::output::OpenUrl)>>,
+    /// consulted with the key and its scancode before `glfw_to_egui_key`. if it returns `None`, we fall back to `glfw_to_egui_key`.
+    /// glfw keys are positional (named after the us layout), so this can be used for layout specific mappings or to remap keys.
+    /// clipboard shortcuts (ctrl + C/X/V) are handled before this.
+    pub key_map_override: Option<Box<dyn Fn(glfw::Key, glfw::Scancode) -> Option<Key>>>,
+    /// if set, copy / cut / paste use this instead of the system clipboard. default is `None` (system clipboard)
+    pub clipboard_handler: Option<Box<dyn ClipboardHandler>>,
+    /// called with true when an egui text field gains focus and with false when it loses focus.
+    /// on touch / kiosk devices without a physical keyboard, use it to show / hide your own on-screen keyboard.
+    /// see `GlfwBackend::egui_wants_text_input`
+    pub soft_keyboard_callback: Option<Box<dyn FnMut(bool)>>,
+}
+impl Default for GlfwConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            title: "Overlay Window".to_string(),
+            window_mode: GlfwWindowMode::Windowed,
+            enable_gamepad: false,
+            decorations: true,
+            resizable: true,
+            render_only_if_dirty: false,
+            auto_suspend_after: None,
+            coalesce_pointer_moves: true,
+            glfw_callback: None,
+            window_callback: None,
+            open_url_callback: None,
+            key_map_override: None,
+            clipboard_handler: None,
+            soft_keyboard_callback: None,
+        }
+    }
 }
 impl WindowBackend for GlfwBackend {
     type Configuration = GlfwConfig;
@@ -63,19 +229,43 @@ impl WindowBackend for GlfwBackend {
                 glfw_context.window_hint(WindowHint::ClientApi(ClientApiHint::NoApi));
             }
         }
+        glfw_context.window_hint(WindowHint::Decorated(config.decorations));
+        glfw_context.window_hint(WindowHint::Resizable(config.resizable));
         if let Some(glfw_callback) = config.glfw_callback {
             glfw_callback(&mut glfw_context);
         }
         // create a window
-        let (mut window, events_receiver) = glfw_context
-            .create_window(800, 600, "Overlay Window", glfw::WindowMode::Windowed)
-            .expect("failed to create glfw window");
+        let (mut window, events_receiver) = match config.window_mode {
+            GlfwWindowMode::Windowed => glfw_context.create_window(
+                config.width,
+                config.height,
+                &config.title,
+                glfw::WindowMode::Windowed,
+            ),
+            GlfwWindowMode::FullscreenPrimaryMonitor => {
+                glfw_context.with_primary_monitor(|glfw_context, monitor| {
+                    glfw_context.create_window(
+                        config.width,
+                        config.height,
+                        &config.title,
+                        monitor.map_or(glfw::WindowMode::Windowed, |monitor| {
+                            glfw::WindowMode::FullScreen(monitor)
+                        }),
+                    )
+                })
+            }
+        }
+        .expect("failed to create glfw window");
         if let GfxApiType::GL = backend_config.gfx_api_type {
             window.make_current();
         }
         // set which events you care about
         window.set_all_polling(true);
         window.set_store_lock_key_mods(true);
+        // only takes effect when the cursor is disabled
+        if glfw_context.supports_raw_motion() {
+            window.set_raw_mouse_motion(true);
+        }
         if let Some(window_callback) = config.window_callback {
             window_callback(&mut window);
         }
@@ -84,32 +274,74 @@ impl WindowBackend for GlfwBackend {
         let scale = window.get_content_scale();
         let cursor_position = window.get_cursor_pos();
         let size_physical_pixels = [width as u32, height as u32];
-        let mut raw_input = RawInput::default();
-        // set raw input screen rect details so that first frame
-        // will have correct size even without any resize event
-        raw_input.screen_rect = Some(egui::Rect::from_points(&[
-            Default::default(),
-            [width as f32 / scale.0, height as f32 / scale.0].into(),
-        ]));
-        raw_input.pixels_per_point = Some(scale.0);
-        Self {
+        let last_input_time = glfw_context.get_time();
+        let mut backend = Self {
             glfw: glfw_context,
             events_receiver,
             window,
             size_physical_pixels,
             scale: [scale.0, scale.1],
-            cursor_pos_physical_pixels: [cursor_position.0 as f32, cursor_position.1 as f32],
-            raw_input,
+            scale_override: None,
+            virtual_screen_size: None,
+            // glfw's cursor position is in screen coordinates
+            cursor_pos_physical_pixels: [
+                cursor_position.0 as f32 * scale.0,
+                cursor_position.1 as f32 * scale.1,
+            ],
+            cursor_pos_screen_coordinates: [cursor_position.0, cursor_position.1],
+            raw_mouse_delta: [0.0, 0.0],
+            frame_mouse_delta: [0.0, 0.0],
+            pending_frame_mouse_delta: [0.0, 0.0],
+            raw_input: RawInput::default(),
+            last_frame_time: None,
+            pressed_keys: HashSet::new(),
+            enable_gamepad: config.enable_gamepad,
+            gamepad_buttons_pressed: HashSet::new(),
             frame_events: vec![],
             resized_event_pending: true, // provide so that on first prepare frame, renderers can set their viewport sizes
+            close_requested: false,
+            minimized: false,
+            min_inner_size: None,
+            max_inner_size: None,
+            render_only_if_dirty: config.render_only_if_dirty,
+            frame_dirty_tracker: FrameDirtyTracker::default(),
+            auto_suspend_after: config.auto_suspend_after,
+            coalesce_pointer_moves: config.coalesce_pointer_moves,
+            last_input_time,
+            auto_suspended: false,
             backend_config,
             cursor_icon: StandardCursor::Arrow,
-        }
+            egui_wants_pointer_input: false,
+            egui_wants_keyboard_input: false,
+            egui_wants_text_input: false,
+            soft_keyboard_callback: config.soft_keyboard_callback,
+            open_url_callback: config.open_url_callback,
+            key_map_override: config.key_map_override,
+            clipboard_handler: config.clipboard_handler,
+        };
+        // set raw input screen rect details so that first frame
+        // will have correct size even without any resize event
+        backend.update_screen_rect();
+        backend
     }
 
     fn take_raw_input(&mut self) -> RawInput {
+        // egui needs time for double clicks, animations etc.. glfw time is monotonic and starts at zero when glfw is initialized
+        let time = self.glfw.get_time();
+        if let Some(last_frame_time) = self.last_frame_time {
+            self.raw_input.predicted_dt = ((time - last_frame_time) as f32).min(MAX_PREDICTED_DT);
+        }
+        self.last_frame_time = Some(time);
+        self.raw_input.time = Some(time);
+        self.frame_mouse_delta = std::mem::take(&mut self.pending_frame_mouse_delta);
         self.raw_input.take()
     }
+    fn raw_input_mut(&mut self) -> Option<&mut RawInput> {
+        Some(&mut self.raw_input)
+    }
+    fn scale_factor(&self) -> [f32; 2] {
+        self.scale
+    }
     fn get_window(&mut self) -> Option<&mut Self::WindowType> {
         Some(&mut self.window)
     }
@@ -124,39 +356,16 @@ impl WindowBackend for GlfwBackend {
         mut self,
         mut gfx_backend: G,
         mut user_app: U,
-    ) {
+    ) -> Result<(), AppError> {
         let egui_context = egui::Context::default();
-        while !self.window.should_close() {
-            // gather events
-            self.tick();
-            // take egui input
-            let raw_input = self.take_raw_input();
-            // take any frambuffer resize events
-
-            // prepare surface for drawing
-            gfx_backend.prepare_frame(self.resized_event_pending, &mut self);
-            self.resized_event_pending = false;
-            // run userapp gui function. let user do anything he wants with window or gfx backends
-            let output = user_app.run(&egui_context, raw_input, &mut self, &mut gfx_backend);
-            if !output.platform_output.copied_text.is_empty() {
-                self.window
-                    .set_clipboard_string(&output.platform_output.copied_text);
+        loop {
+            match self.pump_frame(&egui_context, &mut gfx_backend, &mut user_app) {
+                AppControlFlow::Continue => {}
+                AppControlFlow::Exit => break,
+                AppControlFlow::Error(e) => return Err(e),
             }
-            self.set_cursor(output.platform_output.cursor_icon);
-            // prepare egui render data for gfx backend
-            let egui_gfx_data = EguiGfxData {
-                meshes: egui_context.tessellate(output.shapes),
-                textures_delta: output.textures_delta,
-                screen_size_logical: [
-                    self.size_physical_pixels[0] as f32 / self.scale[0],
-                    self.size_physical_pixels[1] as f32 / self.scale[0],
-                ],
-            };
-            // render egui with gfx backend
-            gfx_backend.render(egui_gfx_data);
-            // present the frame and loop back
-            gfx_backend.present(&mut self);
         }
+        Ok(())
     }
 
     fn get_config(&self) -> &BackendConfig {
@@ -170,9 +379,289 @@ impl WindowBackend for GlfwBackend {
     fn get_proc_address(&mut self, symbol: &str) -> *const core::ffi::c_void {
         self.window.get_proc_address(symbol)
     }
+
+    fn set_cursor_grab(&mut self, mode: CursorGrabMode) {
+        let cursor_mode = match mode {
+            CursorGrabMode::None => glfw::CursorMode::Normal,
+            CursorGrabMode::Confined => {
+                tracing::warn!("glfw doesn't support confining the cursor. locking it instead");
+                glfw::CursorMode::Disabled
+            }
+            CursorGrabMode::Locked => glfw::CursorMode::Disabled,
+        };
+        self.window.set_cursor_mode(cursor_mode);
+    }
+
+    /// glfw has no raw motion event. while the cursor is disabled (`CursorGrabMode::Locked` or `Confined`) and raw mouse motion
+    /// is enabled (on by default if `Glfw::supports_raw_motion`), glfw accumulates the raw device motion into the virtual cursor
+    /// position, so the difference of the positions is the raw delta. otherwise, the position is scaled and accelerated by
+    /// the os. so, this is zero unless the cursor is disabled and raw motion is enabled.
+    fn take_raw_mouse_delta(&mut self) -> [f64; 2] {
+        std::mem::take(&mut self.raw_mouse_delta)
+    }
+
+    /// same conditions as `WindowBackend::take_raw_mouse_delta`. zero unless the cursor is disabled and raw motion is enabled.
+    fn frame_mouse_delta(&self) -> [f64; 2] {
+        self.frame_mouse_delta
+    }
+
+    fn set_scale_factor_override(&mut self, scale: Option<f32>) {
+        self.scale_override = scale;
+        let (x, y) = self.window.get_content_scale();
+        self.scale = scale.map_or([x, y], |s| [s, s]);
+        self.update_screen_rect();
+        self.resized_event_pending = true;
+    }
+
+    fn set_decorations(&mut self, decorations: bool) {
+        self.window.set_decorated(decorations);
+    }
+
+    fn set_resizable(&mut self, resizable: bool) {
+        self.window.set_resizable(resizable);
+    }
+
+    fn set_virtual_screen_size(&mut self, size: Option<[f32; 2]>) {
+        self.virtual_screen_size = size;
+        self.update_screen_rect();
+        self.resized_event_pending = true;
+    }
+
+    fn set_min_inner_size(&mut self, size: Option<[f32; 2]>) {
+        self.min_inner_size = size;
+        self.apply_size_limits();
+    }
+
+    fn set_max_inner_size(&mut self, size: Option<[f32; 2]>) {
+        self.max_inner_size = size;
+        self.apply_size_limits();
+    }
+
+    fn set_sticky(&mut self, sticky: bool) {
+        #[cfg(target_os = "linux")]
+        if let Err(e) = set_x11_sticky(
+            self.glfw.get_x11_display(),
+            self.window.get_x11_window(),
+            sticky,
+        ) {
+            tracing::warn!("failed to make the window sticky: {e}");
+        }
+        #[cfg(not(target_os = "linux"))]
+        tracing::warn!(
+            "sticky windows are only supported on linux with x11. ignoring set_sticky({sticky})"
+        );
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        // disabled (grabbed) cursor is always hidden. don't release the grab just to change visibility
+        if self.window.get_cursor_mode() == glfw::CursorMode::Disabled {
+            return;
+        }
+        self.window.set_cursor_mode(if visible {
+            glfw::CursorMode::Normal
+        } else {
+            glfw::CursorMode::Hidden
+        });
+    }
 }
 
 impl GlfwBackend {
+    /// does the work of a single frame: gathers events, runs the user app, renders and presents.
+    /// `WindowBackend::run_event_loop` just calls this in a loop. if you want to drive the loop yourself
+    /// (eg: embedding inside another application's main loop), call this once per iteration with the same `egui_context`.
+    ///
+    /// returns `AppControlFlow::Exit` if the window should close, otherwise whatever `UserAppData::control_flow` returns.
+    /// close requests go through `UserAppData::on_close_requested` first. so, the window closes in the next call if it returns true.
+    /// while the window is minimized, this waits for events (with a small timeout) and returns without rendering.
+    /// while auto suspended (see `GlfwConfig::auto_suspend_after`), this waits for events and resumes on the next input.
+    pub fn pump_frame<G: GfxBackend<Self>, U: UserAppData<Self, G>>(
+        &mut self,
+        egui_context: &egui::Context,
+        gfx_backend: &mut G,
+        user_app: &mut U,
+    ) -> AppControlFlow {
+        if self.window.should_close() {
+            return AppControlFlow::Exit;
+        }
+        // gather events
+        self.tick();
+        if std::mem::take(&mut self.close_requested) && !user_app.on_close_requested() {
+            // glfw already set the close flag. the app wants to keep running (eg: to ask for confirmation)
+            self.window.set_should_close(false);
+        }
+        if self.update_auto_suspend(gfx_backend) {
+            // nothing to draw until the next input. `GlfwWakeHandle` can still wake us up
+            self.glfw.wait_events();
+            return AppControlFlow::Continue;
+        }
+        // skip rendering while minimized. input will be kept around until we are restored.
+        if self.minimized {
+            // don't spin the cpu while waiting for restore. events are still queued for the next tick
+            self.glfw.wait_events_timeout(0.1);
+            return AppControlFlow::Continue;
+        }
+        // take egui input
+        let raw_input = self.take_raw_input();
+
+        let resized = self.resized_event_pending;
+        // prepare surface for drawing
+        gfx_backend.prepare_frame(resized, self);
+        self.resized_event_pending = false;
+        // run userapp gui function. let user do anything he wants with window or gfx backends
+        let output = user_app.run(egui_context, raw_input, self, gfx_backend);
+        self.egui_wants_pointer_input = egui_context.wants_pointer_input();
+        self.egui_wants_keyboard_input = egui_context.wants_keyboard_input();
+        let wants_text_input = output.platform_output.text_cursor_pos.is_some();
+        if self.egui_wants_text_input != wants_text_input {
+            self.egui_wants_text_input = wants_text_input;
+            if let Some(soft_keyboard_callback) = self.soft_keyboard_callback.as_mut() {
+                soft_keyboard_callback(wants_text_input);
+            }
+        }
+        if !output.platform_output.copied_text.is_empty() {
+            self.set_clipboard_text(&output.platform_output.copied_text);
+        }
+        self.set_cursor(output.platform_output.cursor_icon);
+        // window backends don't use these events (accessibility etc..). they are dropped along with the output.
+        if !output.platform_output.events.is_empty() {
+            tracing::trace!(
+                "ignoring egui output events: {}",
+                output.platform_output.events_description()
+            );
+        }
+        if let Some(open_url) = output.platform_output.open_url.as_ref() {
+            self.open_url(open_url);
+        }
+        let letterbox = self.letterbox();
+        let screen_size_logical = letterbox.map_or(
+            self.physical_to_logical([
+                self.size_physical_pixels[0] as f32,
+                self.size_physical_pixels[1] as f32,
+            ]),
+            |letterbox| letterbox.virtual_size,
+        );
+        if self.render_only_if_dirty
+            && !self.frame_dirty_tracker.is_dirty(
+                &output,
+                screen_size_logical,
+                egui_context.pixels_per_point(),
+                resized,
+            )
+            && gfx_backend.discard_frame(self)
+        {
+            return user_app.control_flow();
+        }
+        // prepare egui render data for gfx backend
+        let egui_gfx_data = EguiGfxData {
+            meshes: egui_context.tessellate(output.shapes),
+            textures_delta: output.textures_delta,
+            screen_size_logical,
+            viewport: letterbox.map(|letterbox| letterbox.viewport),
+        };
+        // render egui with gfx backend
+        gfx_backend.render(egui_gfx_data);
+        // present the frame and loop back
+        gfx_backend.present(self);
+        user_app.control_flow()
+    }
+    /// pushes an event into the next frame's input, coalescing pointer moves if `Self::coalesce_pointer_moves` is set
+    fn push_input_event(&mut self, event: Event) {
+        if self.coalesce_pointer_moves {
+            push_coalesced_event(&mut self.raw_input.events, event);
+        } else {
+            self.raw_input.events.push(event);
+        }
+    }
+    /// tracks the time of the last input and suspends / resumes the gfx backend for `Self::auto_suspend_after`.
+    /// resumes before the input reaches egui, so that the frame which handles it can be drawn. returns true while suspended.
+    fn update_auto_suspend<G: GfxBackend<Self>>(&mut self, gfx_backend: &mut G) -> bool {
+        let now = self.glfw.get_time();
+        if !self.raw_input.events.is_empty() || !self.raw_input.dropped_files.is_empty() {
+            self.last_input_time = now;
+            if self.auto_suspended {
+                tracing::debug!("resuming after input");
+                self.auto_suspended = false;
+                gfx_backend.resume(self);
+                // the window might have been resized while suspended
+                self.resized_event_pending = true;
+            }
+        }
+        if let Some(auto_suspend_after) = self.auto_suspend_after {
+            if !self.auto_suspended
+                && now - self.last_input_time >= auto_suspend_after.as_secs_f64()
+            {
+                tracing::debug!("suspending after {auto_suspend_after:?} without input");
+                self.auto_suspended = true;
+                gfx_backend.suspend(self);
+            }
+        }
+        self.auto_suspended
+    }
+    /// glfw sets both limits at once. so, we keep them around in `Self::min_inner_size` and `Self::max_inner_size`.
+    fn apply_size_limits(&mut self) {
+        // glfw's size limits are in screen coordinates. we treat them as logical, just like `CursorPos`.
+        // so, go through physical pixels and the window's content scale, in case the scale is overridden.
+        let (content_scale_x, content_scale_y) = self.window.get_content_scale();
+        let to_screen_coordinates = |size: Option<[f32; 2]>| {
+            size.map(|size| {
+                let [width, height] = self.logical_to_physical(size);
+                [
+                    (width / content_scale_x).round() as u32,
+                    (height / content_scale_y).round() as u32,
+                ]
+            })
+        };
+        let min = to_screen_coordinates(self.min_inner_size);
+        let max = to_screen_coordinates(self.max_inner_size);
+        self.window.set_size_limits(
+            min.map(|size| size[0]),
+            min.map(|size| size[1]),
+            max.map(|size| size[0]),
+            max.map(|size| size[1]),
+        );
+    }
+    /// `Some` if `Self::virtual_screen_size` is set
+    pub fn letterbox(&self) -> Option<Letterbox> {
+        self.virtual_screen_size
+            .map(|virtual_size| Letterbox::new(virtual_size, self.size_physical_pixels))
+    }
+    /// sets `raw_input.screen_rect` and `raw_input.pixels_per_point` using the framebuffer size and scale or the virtual screen size
+    fn update_screen_rect(&mut self) {
+        if let Some(letterbox) = self.letterbox() {
+            self.raw_input.screen_rect = Some(egui::Rect::from_two_pos(
+                Default::default(),
+                letterbox.virtual_size.into(),
+            ));
+            self.raw_input.pixels_per_point = Some(letterbox.pixels_per_point);
+        } else {
+            let logical_size = self.physical_to_logical([
+                self.size_physical_pixels[0] as f32,
+                self.size_physical_pixels[1] as f32,
+            ]);
+            self.raw_input.screen_rect = Some(egui::Rect::from_two_pos(
+                Default::default(),
+                logical_size.into(),
+            ));
+            self.raw_input.pixels_per_point = Some(self.scale[0]);
+        }
+    }
+    /// glfw's cursor positions are in screen coordinates, which are scaled by the window's content scale. so, we convert
+    /// them with the content scale instead of `Self::scale`, which might be overridden (see `WindowBackend::set_scale_factor_override`).
+    /// the override is applied later, when `Self::cursor_pos_to_egui` converts the physical position into points.
+    fn screen_coordinates_to_physical(&self, screen_coordinates: [f32; 2]) -> [f32; 2] {
+        let (content_scale_x, content_scale_y) = self.window.get_content_scale();
+        [
+            screen_coordinates[0] * content_scale_x,
+            screen_coordinates[1] * content_scale_y,
+        ]
+    }
+    /// converts the cursor position in physical pixels to egui's coordinates (logical or virtual screen space)
+    fn cursor_pos_to_egui(&self, physical: [f32; 2]) -> Pos2 {
+        match self.letterbox() {
+            Some(letterbox) => letterbox.physical_to_virtual(physical),
+            None => self.physical_to_logical(physical).into(),
+        }
+    }
     pub fn tick(&mut self) {
         self.glfw.poll_events();
         self.frame_events.clear();
@@ -180,7 +669,11 @@ impl GlfwBackend {
         // if false, and the window is passthrough, we will manually get cursor pos and push it
         // otherwise, we do nothing.
         let mut cursor_event = false;
-        for (_, event) in glfw::flush_messages(&self.events_receiver) {
+        // collect the events first. the receiver borrows `self`, but handling events needs `&mut self`
+        let events: Vec<WindowEvent> = glfw::flush_messages(&self.events_receiver)
+            .map(|(_, event)| event)
+            .collect();
+        for event in events {
             self.frame_events.push(event.clone());
             // if let &glfw::WindowEvent::CursorPos(..) = &event {
             //     continue;
@@ -190,19 +683,13 @@ impl GlfwBackend {
                 glfw::WindowEvent::FramebufferSize(w, h) => {
                     self.size_physical_pixels = [w as u32, h as u32];
                     self.resized_event_pending = true;
-                    self.raw_input.screen_rect = Some(egui::Rect::from_two_pos(
-                        Default::default(),
-                        [w as f32 / self.scale[0], h as f32 / self.scale[1]].into(),
-                    ));
+                    self.update_screen_rect();
 
                     None
                 }
                 glfw::WindowEvent::MouseButton(mb, a, m) => {
                     let emb = Event::PointerButton {
-                        pos: Pos2 {
-                            x: self.cursor_pos_physical_pixels[0] / self.scale[0],
-                            y: self.cursor_pos_physical_pixels[1] / self.scale[1],
-                        },
+                        pos: self.cursor_pos_to_egui(self.cursor_pos_physical_pixels),
                         button: glfw_to_egui_pointer_button(mb),
                         pressed: glfw_to_egui_action(a),
                         modifiers: glfw_to_egui_modifers(m),
@@ -211,49 +698,88 @@ impl GlfwBackend {
                 }
                 // we scroll 25 pixels at a time
                 glfw::WindowEvent::Scroll(x, y) => {
-                    Some(Event::Scroll([x as f32 * 25.0, y as f32 * 25.0].into()))
+                    // glfw doesn't give us modifiers with scroll events. so, we ask the window directly
+                    let ctrl = self.window.get_key(glfw::Key::LeftControl) != Action::Release
+                        || self.window.get_key(glfw::Key::RightControl) != Action::Release;
+                    // ctrl + scroll is zoom, just like egui's own winit integration
+                    if ctrl {
+                        Some(Event::Zoom((y as f32 * 0.1).exp()))
+                    } else {
+                        // glfw gives us the offsets in lines
+                        let points_per_line = self.backend_config.scroll_points_per_line;
+                        Some(Event::Scroll(
+                            [x as f32 * points_per_line, y as f32 * points_per_line].into(),
+                        ))
+                    }
                 }
-                glfw::WindowEvent::Key(k, _, a, m) => match k {
-                    glfw::Key::C => {
-                        if glfw_to_egui_action(a) && m.contains(glfw::Modifiers::Control) {
-                            Some(Event::Copy)
-                        } else {
-                            None
+                glfw::WindowEvent::Key(k, scancode, a, m) => {
+                    // AltGr is reported as Ctrl + Alt on windows. so, we don't treat AltGr + C/X/V as clipboard shortcuts
+                    // and let them fall through to the `Char` event which produces the real character (eg: `@` or `{`)
+                    // `command` is cmd on mac and ctrl elsewhere, the same modifier that egui checks for its own shortcuts
+                    let clipboard_shortcut = glfw_to_egui_action(a)
+                        && glfw_to_egui_modifers(m).command
+                        && !m.contains(glfw::Modifiers::Alt);
+                    let clipboard_event = match k {
+                        glfw::Key::C => {
+                            if clipboard_shortcut {
+                                Some(Event::Copy)
+                            } else {
+                                None
+                            }
                         }
-                    }
-                    glfw::Key::X => {
-                        if glfw_to_egui_action(a) && m.contains(glfw::Modifiers::Control) {
-                            Some(Event::Cut)
-                        } else {
-                            None
+                        glfw::Key::X => {
+                            if clipboard_shortcut {
+                                Some(Event::Cut)
+                            } else {
+                                None
+                            }
                         }
-                    }
-                    glfw::Key::V => {
-                        if glfw_to_egui_action(a) && m.contains(glfw::Modifiers::Control) {
-                            Some(Event::Text(
-                                self.window.get_clipboard_string().unwrap_or_default(),
-                            ))
-                        } else {
-                            None
+                        glfw::Key::V => {
+                            if clipboard_shortcut {
+                                self.get_clipboard_text().map(Event::Paste)
+                            } else {
+                                None
+                            }
                         }
-                    }
-                    _ => None,
-                }
-                .or_else(|| {
-                    glfw_to_egui_key(k).map(|key| Event::Key {
-                        key,
-                        pressed: glfw_to_egui_action(a),
-                        modifiers: glfw_to_egui_modifers(m),
+                        _ => None,
+                    };
+                    clipboard_event.or_else(|| {
+                        self.key_map_override
+                            .as_ref()
+                            .and_then(|key_map_override| key_map_override(k, scancode))
+                            .or_else(|| glfw_to_egui_key(k))
+                            .map(|key| Event::Key {
+                                key,
+                                pressed: glfw_to_egui_action(a),
+                                modifiers: glfw_to_egui_modifers(m),
+                            })
                     })
-                }),
+                }
                 glfw::WindowEvent::Char(c) => Some(Event::Text(c.to_string())),
                 glfw::WindowEvent::ContentScale(x, y) => {
-                    self.raw_input.pixels_per_point = Some(x);
-                    self.scale = [x, y];
+                    self.scale = self.scale_override.map_or([x, y], |s| [s, s]);
+                    self.update_screen_rect();
                     None
                 }
                 glfw::WindowEvent::Close => {
                     self.window.set_should_close(true);
+                    self.close_requested = true;
+                    None
+                }
+                glfw::WindowEvent::Iconify(iconified) => {
+                    self.minimized = iconified;
+                    // size might have changed while minimized. so, renderers should check the size again on restore.
+                    if !iconified {
+                        self.resized_event_pending = true;
+                    }
+                    None
+                }
+                glfw::WindowEvent::Focus(focused) => {
+                    self.raw_input.has_focus = focused;
+                    // we won't get release events for keys released while we are not focused (eg: alt + tab)
+                    if !focused {
+                        self.release_pressed_keys();
+                    }
                     None
                 }
 
@@ -271,32 +797,131 @@ impl GlfwBackend {
                 glfw::WindowEvent::CursorPos(x, y) => {
                     cursor_event = true;
                     self.cursor_pos_physical_pixels =
-                        [x as f32 * self.scale[0], y as f32 * self.scale[1]];
-                    Some(egui::Event::PointerMoved([x as f32, y as f32].into()))
+                        self.screen_coordinates_to_physical([x as f32, y as f32]);
+                    Some(egui::Event::PointerMoved(
+                        self.cursor_pos_to_egui(self.cursor_pos_physical_pixels),
+                    ))
                 }
                 _rest => None,
             } {
-                self.raw_input.events.push(ev);
+                if let Event::Key { key, pressed, .. } = ev {
+                    if pressed {
+                        self.pressed_keys.insert(key);
+                    } else {
+                        self.pressed_keys.remove(&key);
+                    }
+                }
+                self.push_input_event(ev);
             }
         }
 
-        let cursor_position = self.window.get_cursor_pos();
-        let cursor_position = [cursor_position.0 as f32, cursor_position.1 as f32];
+        let (cursor_x, cursor_y) = self.window.get_cursor_pos();
+        // glfw gives us the cursor position in screen coordinates. we store it in physical pixels, just like `CursorPos` events
+        let cursor_position =
+            self.screen_coordinates_to_physical([cursor_x as f32, cursor_y as f32]);
         // when there's no cursor event and cursor position has changed and window is passthrough
         if !cursor_event
             && cursor_position != self.cursor_pos_physical_pixels
             && self.window.is_mouse_passthrough()
         {
             // we will manually push the cursor moved event.
-            self.raw_input.events.push(Event::PointerMoved(
-                [
-                    cursor_position[0] / self.scale[0],
-                    cursor_position[1] / self.scale[1],
-                ]
-                .into(),
-            ))
+            let pos = self.cursor_pos_to_egui(cursor_position);
+            self.push_input_event(Event::PointerMoved(pos))
         }
+        // with a disabled cursor and raw mouse motion, glfw keeps moving the virtual cursor position by the raw device motion
+        // without bounds. so, the difference (in unscaled screen coordinates) is the raw motion for this frame.
+        // otherwise, the position is scaled / accelerated by the os and isn't raw motion. see `WindowBackend::take_raw_mouse_delta`
+        if self.window.get_cursor_mode() == glfw::CursorMode::Disabled
+            && self.window.uses_raw_mouse_motion()
+        {
+            let mouse_delta = [
+                cursor_x - self.cursor_pos_screen_coordinates[0],
+                cursor_y - self.cursor_pos_screen_coordinates[1],
+            ];
+            for delta in [
+                &mut self.raw_mouse_delta,
+                &mut self.pending_frame_mouse_delta,
+            ] {
+                delta[0] += mouse_delta[0];
+                delta[1] += mouse_delta[1];
+            }
+        }
+        self.cursor_pos_screen_coordinates = [cursor_x, cursor_y];
         self.cursor_pos_physical_pixels = cursor_position;
+        if self.enable_gamepad {
+            self.poll_gamepads();
+        }
+    }
+    /// checks the state of the first few gamepads and pushes key events for the buttons which were pressed / released since the last tick.
+    /// if multiple gamepads are connected, a button counts as held down if it is held down on any of them.
+    fn poll_gamepads(&mut self) {
+        let mut pressed = HashSet::new();
+        for id in [
+            glfw::JoystickId::Joystick1,
+            glfw::JoystickId::Joystick2,
+            glfw::JoystickId::Joystick3,
+            glfw::JoystickId::Joystick4,
+        ] {
+            let joystick = self.glfw.get_joystick(id);
+            if !joystick.is_gamepad() {
+                continue;
+            }
+            if let Some(state) = joystick.get_gamepad_state() {
+                for (button, _) in GAMEPAD_BUTTON_TO_KEY {
+                    if state.get_button_state(button) != Action::Release {
+                        pressed.insert(button);
+                    }
+                }
+            }
+        }
+        for (button, key) in GAMEPAD_BUTTON_TO_KEY {
+            let was_pressed = self.gamepad_buttons_pressed.contains(&button);
+            let is_pressed = pressed.contains(&button);
+            if was_pressed != is_pressed {
+                self.raw_input.events.push(Event::Key {
+                    key,
+                    pressed: is_pressed,
+                    modifiers: egui::Modifiers::default(),
+                });
+            }
+        }
+        self.gamepad_buttons_pressed = pressed;
+    }
+    /// returns a handle which can be sent to other threads to wake up the event loop.
+    pub fn wake_handle(&self) -> GlfwWakeHandle {
+        GlfwWakeHandle { _private: () }
+    }
+    /// pushes release events for all the keys which are still held down
+    fn release_pressed_keys(&mut self) {
+        for key in self.pressed_keys.drain() {
+            self.raw_input.events.push(Event::Key {
+                key,
+                pressed: false,
+                modifiers: egui::Modifiers::default(),
+            });
+        }
+    }
+    fn get_clipboard_text(&mut self) -> Option<String> {
+        match self.clipboard_handler.as_mut() {
+            Some(clipboard_handler) => clipboard_handler.get(),
+            None => self.window.get_clipboard_string(),
+        }
+    }
+    fn set_clipboard_text(&mut self, text: &str) {
+        match self.clipboard_handler.as_mut() {
+            Some(clipboard_handler) => clipboard_handler.set(text.to_string()),
+            None => self.window.set_clipboard_string(text),
+        }
+    }
+    fn open_url(&mut self, open_url: &egui::output::OpenUrl) {
+        if let Some(open_url_callback) = self.open_url_callback.as_mut() {
+            open_url_callback(open_url);
+        } else {
+            tracing::warn!(
+                "ignoring url {} as there's no open url callback",
+                open_url.url
+            );
+        }
     }
     fn set_cursor(&mut self, cursor: egui::CursorIcon) {
         let cursor = egui_to_glfw_cursor(cursor);
@@ -307,20 +932,30 @@ impl GlfwBackend {
     }
 }
 
+/// gamepad buttons which are translated into egui key events when `GlfwConfig::enable_gamepad` is true.
+/// D-pad moves like arrow keys, A (bottom face button) confirms like Enter and B (right face button) goes back like Escape.
+pub const GAMEPAD_BUTTON_TO_KEY: [(glfw::GamepadButton, Key); 6] = [
+    (glfw::GamepadButton::ButtonDpadUp, Key::ArrowUp),
+    (glfw::GamepadButton::ButtonDpadDown, Key::ArrowDown),
+    (glfw::GamepadButton::ButtonDpadLeft, Key::ArrowLeft),
+    (glfw::GamepadButton::ButtonDpadRight, Key::ArrowRight),
+    (glfw::GamepadButton::ButtonA, Key::Enter),
+    (glfw::GamepadButton::ButtonB, Key::Escape),
+];
 /// a function to get the matching egui key event for a given glfw key. egui does not support all the keys provided here.
 fn glfw_to_egui_key(key: glfw::Key) -> Option<Key> {
     match key {
         glfw::Key::Space => Some(Key::Space),
-        glfw::Key::Num0 => Some(Key::Num0),
-        glfw::Key::Num1 => Some(Key::Num1),
-        glfw::Key::Num2 => Some(Key::Num2),
-        glfw::Key::Num3 => Some(Key::Num3),
-        glfw::Key::Num4 => Some(Key::Num4),
-        glfw::Key::Num5 => Some(Key::Num5),
-        glfw::Key::Num6 => Some(Key::Num6),
-        glfw::Key::Num7 => Some(Key::Num7),
-        glfw::Key::Num8 => Some(Key::Num8),
-        glfw::Key::Num9 => Some(Key::Num9),
+        glfw::Key::Num0 | glfw::Key::Kp0 => Some(Key::Num0),
+        glfw::Key::Num1 | glfw::Key::Kp1 => Some(Key::Num1),
+        glfw::Key::Num2 | glfw::Key::Kp2 => Some(Key::Num2),
+        glfw::Key::Num3 | glfw::Key::Kp3 => Some(Key::Num3),
+        glfw::Key::Num4 | glfw::Key::Kp4 => Some(Key::Num4),
+        glfw::Key::Num5 | glfw::Key::Kp5 => Some(Key::Num5),
+        glfw::Key::Num6 | glfw::Key::Kp6 => Some(Key::Num6),
+        glfw::Key::Num7 | glfw::Key::Kp7 => Some(Key::Num7),
+        glfw::Key::Num8 | glfw::Key::Kp8 => Some(Key::Num8),
+        glfw::Key::Num9 | glfw::Key::Kp9 => Some(Key::Num9),
         glfw::Key::A => Some(Key::A),
         glfw::Key::B => Some(Key::B),
         glfw::Key::C => Some(Key::C),
@@ -348,7 +983,7 @@ fn glfw_to_egui_key(key: glfw::Key) -> Option<Key> {
         glfw::Key::Y => Some(Key::Y),
         glfw::Key::Z => Some(Key::Z),
         glfw::Key::Escape => Some(Key::Escape),
-        glfw::Key::Enter => Some(Key::Enter),
+        glfw::Key::Enter | glfw::Key::KpEnter => Some(Key::Enter),
         glfw::Key::Tab => Some(Key::Tab),
         glfw::Key::Backspace => Some(Key::Backspace),
         glfw::Key::Insert => Some(Key::Insert),
@@ -361,18 +996,90 @@ fn glfw_to_egui_key(key: glfw::Key) -> Option<Key> {
         glfw::Key::PageDown => Some(Key::PageDown),
         glfw::Key::Home => Some(Key::Home),
         glfw::Key::End => Some(Key::End),
+        // egui 0.20 doesn't have keys for numpad add / subtract / multiply / divide.
+        // their characters still reach egui as `Event::Text` via the `Char` event
         _ => None,
     }
 }
 
+/// `command` is the platform's shortcut modifier: cmd (`Super`) on mac and ctrl everywhere else.
+/// both our clipboard shortcuts and egui's own shortcuts (eg: select all) use it.
 pub fn glfw_to_egui_modifers(modifiers: glfw::Modifiers) -> egui::Modifiers {
+    let ctrl = modifiers.contains(glfw::Modifiers::Control);
+    let mac_cmd = cfg!(target_os = "macos") && modifiers.contains(glfw::Modifiers::Super);
     egui::Modifiers {
         alt: modifiers.contains(glfw::Modifiers::Alt),
-        ctrl: modifiers.contains(glfw::Modifiers::Control),
+        ctrl,
         shift: modifiers.contains(glfw::Modifiers::Shift),
-        mac_cmd: false,
-        command: modifiers.contains(glfw::Modifiers::Control),
+        mac_cmd,
+        command: if cfg!(target_os = "macos") {
+            mac_cmd
+        } else {
+            ctrl
+        },
+    }
+}
+
+/// asks the x11 window manager to add or remove `_NET_WM_STATE_STICKY`, as described by the EWMH spec.
+/// libX11 is loaded at runtime, so this just fails if it's missing.
+#[cfg(target_os = "linux")]
+fn set_x11_sticky(
+    display: *mut std::ffi::c_void,
+    window: *mut std::ffi::c_void,
+    sticky: bool,
+) -> Result<(), String> {
+    use std::os::raw::{c_char, c_long};
+    use x11_dl::xlib;
+    if display.is_null() {
+        return Err("glfw is not using x11".to_string());
+    }
+    let xlib = xlib::Xlib::open().map_err(|e| e.to_string())?;
+    let display = display as *mut xlib::Display;
+    // glfw returns the XID of the window as a pointer
+    let window = window as usize as xlib::Window;
+    unsafe {
+        let wm_state = (xlib.XInternAtom)(
+            display,
+            b"_NET_WM_STATE\0".as_ptr() as *const c_char,
+            xlib::False,
+        );
+        let wm_state_sticky = (xlib.XInternAtom)(
+            display,
+            b"_NET_WM_STATE_STICKY\0".as_ptr() as *const c_char,
+            xlib::False,
+        );
+        let mut data = xlib::ClientMessageData::new();
+        // _NET_WM_STATE_ADD is 1 and _NET_WM_STATE_REMOVE is 0
+        data.set_long(0, sticky as c_long);
+        data.set_long(1, wm_state_sticky as c_long);
+        // source indication: normal application
+        data.set_long(3, 1);
+        let mut event = xlib::XEvent {
+            client_message: xlib::XClientMessageEvent {
+                type_: xlib::ClientMessage,
+                serial: 0,
+                send_event: xlib::True,
+                display,
+                window,
+                message_type: wm_state,
+                format: 32,
+                data,
+            },
+        };
+        // the window manager only sees client messages sent to the root window
+        let sent = (xlib.XSendEvent)(
+            display,
+            (xlib.XDefaultRootWindow)(display),
+            xlib::False,
+            xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+            &mut event,
+        );
+        (xlib.XFlush)(display);
+        if sent == 0 {
+            return Err("XSendEvent failed".to_string());
+        }
     }
+    Ok(())
 }
 
 pub fn glfw_to_egui_pointer_button(mb: glfw::MouseButton) -> PointerButton {
@@ -386,6 +1093,10 @@ pub fn glfw_to_egui_pointer_button(mb: glfw::MouseButton) -> PointerButton {
     }
 }
 
+/// converts glfw action into `pressed` of `egui::Event::Key`.
+/// `Action::Repeat` is treated as a press, so that holding a key down in a text box keeps repeating it.
+/// egui 0.20's `Event::Key` doesn't have a `repeat` field yet (added in 0.21, where egui detects repeats by itself).
+/// so, we can't tell egui whether the press is a repeat until we upgrade egui.
 pub fn glfw_to_egui_action(a: glfw::Action) -> bool {
     match a {
         Action::Release => false,
@@ -103,6 +103,14 @@ impl WindowBackend for Sdl2Backend {
         self.raw_input.take()
     }
 
+    fn raw_input_mut(&mut self) -> Option<&mut RawInput> {
+        Some(&mut self.raw_input)
+    }
+
+    fn scale_factor(&self) -> [f32; 2] {
+        self.scale
+    }
+
     fn get_window(&mut self) -> Option<&mut Self::WindowType> {
         Some(&mut self.window)
     }
@@ -118,11 +126,15 @@ impl WindowBackend for Sdl2Backend {
         mut self,
         mut gfx_backend: G,
         mut user_app: U,
-    ) {
+    ) -> Result<(), AppError> {
         let egui_context = egui::Context::default();
         while !self.should_close {
             // gather events
             self.tick();
+            // `should_close` is only set by the quit / close events here, as we checked it before the tick
+            if self.should_close && !user_app.on_close_requested() {
+                self.should_close = false;
+            }
             // take egui input
             let raw_input = self.take_raw_input();
             // prepare surface for drawing
@@ -145,16 +157,23 @@ impl WindowBackend for Sdl2Backend {
             let egui_gfx_data = EguiGfxData {
                 meshes: egui_context.tessellate(output.shapes),
                 textures_delta: output.textures_delta,
-                screen_size_logical: [
-                    self.size_physical_pixels[0] as f32 / self.scale[0],
-                    self.size_physical_pixels[1] as f32 / self.scale[0],
-                ],
+                screen_size_logical: self.physical_to_logical([
+                    self.size_physical_pixels[0] as f32,
+                    self.size_physical_pixels[1] as f32,
+                ]),
+                viewport: None,
             };
             // render egui with gfx backend
             gfx_backend.render(egui_gfx_data);
             // present the frame and loop back
             gfx_backend.present(&mut self);
+            match user_app.control_flow() {
+                AppControlFlow::Continue => {}
+                AppControlFlow::Exit => break,
+                AppControlFlow::Error(e) => return Err(e),
+            }
         }
+        Ok(())
     }
 
     fn get_config(&self) -> &BackendConfig {
@@ -167,6 +186,33 @@ impl WindowBackend for Sdl2Backend {
     fn get_proc_address(&mut self, symbol: &str) -> *const core::ffi::c_void {
         self.window.subsystem().gl_get_proc_address(symbol) as *const core::ffi::c_void
     }
+
+    fn set_cursor_grab(&mut self, mode: CursorGrabMode) {
+        let mouse = self.sdl_context.mouse();
+        match mode {
+            CursorGrabMode::None => {
+                mouse.set_relative_mouse_mode(false);
+                self.window.set_grab(false);
+            }
+            CursorGrabMode::Confined => {
+                mouse.set_relative_mouse_mode(false);
+                self.window.set_grab(true);
+            }
+            CursorGrabMode::Locked => {
+                mouse.set_relative_mouse_mode(true);
+            }
+        }
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        self.sdl_context.mouse().show_cursor(visible);
+    }
+
+    fn take_raw_mouse_delta(&mut self) -> [f64; 2] {
+        // sdl keeps track of relative motion since the last time we asked for it
+        let state = self.event_pump.relative_mouse_state();
+        [state.x() as f64, state.y() as f64]
+    }
 }
 
 impl Sdl2Backend {
@@ -253,7 +299,7 @@ impl Sdl2Backend {
                         Scancode::V => {
                             if modifiers.ctrl {
                                 match self.window.subsystem().clipboard().clipboard_text() {
-                                    Ok(text) => Some(Event::Text(text)),
+                                    Ok(text) => Some(Event::Paste(text)),
                                     Err(err) => {
                                         tracing::error!(
                                             "failed to get clipboard text due to error: {err}"
@@ -281,42 +327,11 @@ impl Sdl2Backend {
                 } => {
                     let scan_code = scancode.expect("scan code empty");
                     let modifiers = sdl_to_egui_modifiers(keymod);
-                    match scan_code {
-                        Scancode::C => {
-                            if modifiers.ctrl {
-                                Some(Event::Copy)
-                            } else {
-                                None
-                            }
-                        }
-                        Scancode::X => {
-                            if modifiers.ctrl {
-                                Some(Event::Cut)
-                            } else {
-                                None
-                            }
-                        }
-                        Scancode::V => {
-                            if modifiers.ctrl {
-                                Some(Event::Text(
-                                    self.window
-                                        .subsystem()
-                                        .clipboard()
-                                        .clipboard_text()
-                                        .unwrap_or_default(),
-                                ))
-                            } else {
-                                None
-                            }
-                        }
-                        _ => None,
-                    }
-                    .or_else(|| {
-                        sdl_to_egui_key(scan_code).map(|key| Event::Key {
-                            key,
-                            pressed: false,
-                            modifiers,
-                        })
+                    // copy / cut / paste are only emitted on key down. otherwise, we would paste twice
+                    sdl_to_egui_key(scan_code).map(|key| Event::Key {
+                        key,
+                        pressed: false,
+                        modifiers,
                     })
                 }
                 sdl2::event::Event::TextInput { text, .. } => Some(Event::Text(text)),
@@ -352,7 +367,11 @@ impl Sdl2Backend {
                     }
                 }
                 sdl2::event::Event::MouseWheel { x, y, .. } => {
-                    Some(Event::Scroll([x as f32 * 25.0, y as f32 * 25.0].into()))
+                    // sdl gives us the scroll amount in lines
+                    let points_per_line = self.backend_config.scroll_points_per_line;
+                    Some(Event::Scroll(
+                        [x as f32 * points_per_line, y as f32 * points_per_line].into(),
+                    ))
                 }
 
                 sdl2::event::Event::DropFile { filename, .. } => {
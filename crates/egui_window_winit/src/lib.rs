@@ -1,14 +1,21 @@
 use egui::{DroppedFile, Event, Key, Modifiers, Rect};
 use egui_backend::egui::RawInput;
 use egui_backend::*;
+use std::collections::HashSet;
 pub use winit;
 use winit::{event::MouseButton, window::WindowBuilder, *};
 use winit::{
     event::{ModifiersState, VirtualKeyCode},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget},
 };
+/// whether `WindowBackend::run_event_loop` uses `EventLoopExtRunReturn::run_return` (and returns app errors) on this platform.
+/// otherwise, it uses `EventLoop::run`, which never returns.
+const RUN_RETURN_SUPPORTED: bool = !cfg!(any(
+    target_arch = "wasm32",
+    target_os = "android",
+    target_os = "ios"
+));
 /// config that you provide to winit backend
-#[derive(Debug)]
 pub struct WinitConfig {
     #[cfg(target_os = "android")]
     pub android_app: winit::platform::android::activity::AndroidApp,
@@ -19,12 +26,71 @@ pub struct WinitConfig {
     /// defualt value is : `egui_canvas`
     /// so, make sure there's a canvas element in html body with this id
     pub dom_element_id: Option<String>,
+    /// whether the window has decorations (title bar, borders). default is true.
+    /// can be changed later with `WindowBackend::set_decorations`
+    pub decorations: bool,
+    /// whether the user can resize the window. default is true.
+    /// can be changed later with `WindowBackend::set_resizable`
+    pub resizable: bool,
+    /// don't redraw more often than the refresh rate of the monitor the window is on. default is false.
+    /// present modes like `Fifo` already block at the refresh rate, but only after the frame is submitted.
+    /// this makes the event loop wait instead, so that a fast gpu doesn't queue up frames (and input latency).
+    /// it only delays repaints, so egui's `repaint_after` and `RepaintSignal` still work as usual.
+    pub limit_fps_to_monitor: bool,
+    /// when the event loop redraws the window. default is `ControlFlowMode::Reactive`.
+    pub control_flow_mode: ControlFlowMode,
+    /// skip the frames whose egui output is identical to the previous frame (see `FrameDirtyTracker`). default is false.
+    /// the frame is still prepared, but `GfxBackend::discard_frame` is called instead of rendering and presenting it,
+    /// which releases the swapchain image without showing it. so, the previous frame stays on screen.
+    /// gfx backends which can't discard frames (eg: glow on web) still render them.
+    /// only the egui output is tracked. if you draw anything else in `UserAppData::run`, keep this disabled.
+    pub render_only_if_dirty: bool,
+    /// if set, `GfxBackend::suspend` is called after there's been no input for this long, which releases the surface
+    /// (and other gpu resources of the gfx backend). the next input calls `GfxBackend::resume` before egui sees it.
+    /// no frames are drawn while suspended, so the window shows its last frame (or nothing, depending on the platform).
+    /// useful for overlays which are only interacted with occasionally. wgpu releases its surface,
+    /// while glow and three-d keep their resources and only stop drawing. the gfx backend must implement suspend.
+    /// default is `None`
+    pub auto_suspend_after: Option<std::time::Duration>,
+    /// if the pointer moves several times between two other events (eg: a 1000hz mouse), only keep the last position
+    /// instead of pushing every `Event::PointerMoved` into egui's input. moves around button presses are kept, so clicks
+    /// still happen at the right position. disable it if you need the full motion path (eg: a drawing app). default is true.
+    pub coalesce_pointer_moves: bool,
+    /// egui sets `PlatformOutput::open_url` when a hyperlink is clicked. this callback will be called with that url.
+    /// you can open it with a crate like `webbrowser` or just intercept it.
+    /// if this is `None`, urls are ignored (with a warning).
+    pub open_url_callback: Option<Box<dyn FnMut(&egui::output::OpenUrl)>>,
+    /// consulted before the built-in key mapping. if it returns `None`, we fall back to the built-in mapping.
+    /// use `KeyboardInput::scancode` for positional (layout independent) mappings or `KeyboardInput::virtual_keycode`
+    /// to remap keys. clipboard shortcuts (ctrl/cmd + C/X/V) are handled before this.
+    pub key_map_override: Option<Box<dyn Fn(&event::KeyboardInput) -> Option<Key>>>,
+    /// if set, copy / cut / paste use this instead of the system clipboard. default is `None` (system clipboard)
+    pub clipboard_handler: Option<Box<dyn ClipboardHandler>>,
+    /// called with true when an egui text field gains focus and with false when it loses focus.
+    /// on touch / kiosk devices without a physical keyboard, use it to show / hide your own on-screen keyboard.
+    /// see `WinitBackend::egui_wants_text_input`
+    pub soft_keyboard_callback: Option<Box<dyn FnMut(bool)>>,
+    /// also show / hide android's soft keyboard when an egui text field gains / loses focus. default is false.
+    /// ignored on other platforms, as winit doesn't have an api for the system soft keyboard yet.
+    pub show_soft_keyboard: bool,
 }
 impl Default for WinitConfig {
     fn default() -> Self {
         Self {
             title: "egui winit window".to_string(),
             dom_element_id: Some("egui_canvas".to_string()),
+            decorations: true,
+            resizable: true,
+            limit_fps_to_monitor: false,
+            control_flow_mode: ControlFlowMode::Reactive,
+            render_only_if_dirty: false,
+            auto_suspend_after: None,
+            coalesce_pointer_moves: true,
+            open_url_callback: None,
+            key_map_override: None,
+            clipboard_handler: None,
+            soft_keyboard_callback: None,
+            show_soft_keyboard: false,
             #[cfg(target_os = "android")]
             android_app: unimplemented!(
                 "winit requires android 'app' struct from android_main function"
@@ -32,34 +98,134 @@ impl Default for WinitConfig {
         }
     }
 }
+/// when the winit event loop redraws the window. see `WinitConfig::control_flow_mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlFlowMode {
+    /// redraw continuously, as fast as the present mode allows. useful for games or apps which always animate.
+    Poll,
+    /// only redraw after input, window events or a `RepaintSignal`. egui's `repaint_after` is ignored, so egui's own
+    /// animations (eg: collapsing headers, fading tooltips) only advance while there's input. uses the least power.
+    Wait,
+    /// like `Self::Wait`, but also redraw when egui's `repaint_after` elapses (eg: `Context::request_repaint_after`
+    /// or an ongoing animation).
+    #[default]
+    Reactive,
+}
+/// custom events that we send to winit's event loop using `EventLoopProxy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserEvent {
+    /// wakes up the event loop and redraws the window.
+    Repaint,
+}
+/// A cloneable handle to request a redraw from any thread. get it with `WinitBackend::repaint_signal`.
+///
+/// the event loop waits (`ControlFlow::Wait` / `WaitUntil`) until egui's `repaint_after` elapses or input arrives.
+/// so, if a background task (eg: network response) changes some state shown in the ui, use this to make the
+/// event loop wake up and draw a new frame.
+#[derive(Debug, Clone)]
+pub struct RepaintSignal {
+    proxy: EventLoopProxy<UserEvent>,
+}
+impl RepaintSignal {
+    /// asks the event loop to redraw the window as soon as possible.
+    pub fn request_repaint(&self) {
+        if self.proxy.send_event(UserEvent::Repaint).is_err() {
+            tracing::warn!("failed to request repaint as event loop is already closed");
+        }
+    }
+}
 /// This is the winit WindowBackend for egui
 pub struct WinitBackend {
     /// we want to take out the event loop when we call the  `WindowBackend::run_event_loop` fn
     /// so, this will always be `None` once we start the event loop
-    pub event_loop: Option<EventLoop<()>>,
+    pub event_loop: Option<EventLoop<UserEvent>>,
+    /// we keep a proxy around, so that users can create `RepaintSignal`s even after the event loop is taken
+    pub event_loop_proxy: EventLoopProxy<UserEvent>,
     /// the winit window. on android, this might be None when suspended. and recreated when resumed.
     /// on other platforms, we just create the window before entering event loop.
     pub window: Option<winit::window::Window>,
     /// current modifiers state
     pub modifiers: egui::Modifiers,
+    /// keys which are currently held down. used to release them when the window loses focus
+    pub pressed_keys: HashSet<Key>,
     pub pointer_touch_id: Option<u64>,
     /// frame buffer size in physical pixels
     pub framebuffer_size: [u32; 2],
-    /// scale
+    /// scale. this is the `scale_override` if it is set. otherwise, the window's scale factor
     pub scale: f32,
-    /// cusor position in logical pixels
+    /// set with `WindowBackend::set_scale_factor_override`
+    pub scale_override: Option<f32>,
+    /// set with `WindowBackend::set_virtual_screen_size`
+    pub virtual_screen_size: Option<[f32; 2]>,
+    /// cusor position in logical pixels (or in virtual screen space, if `Self::virtual_screen_size` is set)
     pub cursor_pos_logical: [f32; 2],
+    /// raw mouse motion accumulated from `DeviceEvent::MouseMotion` since the last `WindowBackend::take_raw_mouse_delta`
+    pub raw_mouse_delta: [f64; 2],
+    /// see `WindowBackend::frame_mouse_delta`. updated in `WindowBackend::take_raw_input`
+    pub frame_mouse_delta: [f64; 2],
+    /// relative mouse motion since the last `WindowBackend::take_raw_input`. becomes `Self::frame_mouse_delta`
+    pub pending_frame_mouse_delta: [f64; 2],
     /// input for egui's begin_frame
     pub raw_input: RawInput,
+    /// used to set `RawInput::time` from a monotonic clock
+    pub start_time: instant::Instant,
+    /// time of the previous `WindowBackend::take_raw_input`. used to calculate `RawInput::predicted_dt`
+    pub last_frame_time: Option<f64>,
     /// all current frame's events will be stored in this vec
-    pub frame_events: Vec<winit::event::Event<'static, ()>>,
+    pub frame_events: Vec<winit::event::Event<'static, UserEvent>>,
     /// should be true if there's been a resize event
     /// should be set to false once the renderer takes the latest size during `GfxBackend::prepare_frame`
     pub latest_resize_event: bool,
     /// ???
     pub should_close: bool,
+    /// true while the window is fully hidden behind other windows (`WindowEvent::Occluded`).
+    /// we don't redraw while occluded to save power. not all platforms report occlusion.
+    pub occluded: bool,
     pub backend_config: BackendConfig,
     pub window_builder: WindowBuilder,
+    /// taken from `WinitConfig::limit_fps_to_monitor`. can be changed at runtime.
+    pub limit_fps_to_monitor: bool,
+    /// taken from `WinitConfig::control_flow_mode`. can be changed at runtime.
+    pub control_flow_mode: ControlFlowMode,
+    /// when we started the last redraw. used with `Self::limit_fps_to_monitor`
+    pub last_redraw: Option<instant::Instant>,
+    /// whether the IME is currently enabled on the window. we enable it only while egui has a focused text field.
+    pub ime_allowed: bool,
+    /// set with `WindowBackend::set_min_inner_size`. in logical pixels
+    pub min_inner_size: Option<[f32; 2]>,
+    /// set with `WindowBackend::set_max_inner_size`. in logical pixels
+    pub max_inner_size: Option<[f32; 2]>,
+    /// taken from `WinitConfig::render_only_if_dirty`. can be changed at runtime.
+    pub render_only_if_dirty: bool,
+    /// used with `Self::render_only_if_dirty`
+    pub frame_dirty_tracker: FrameDirtyTracker,
+    /// taken from `WinitConfig::coalesce_pointer_moves`. can be changed at runtime.
+    pub coalesce_pointer_moves: bool,
+    /// taken from `WinitConfig::auto_suspend_after`. can be changed at runtime.
+    pub auto_suspend_after: Option<std::time::Duration>,
+    /// time of the most recent input event. used with `Self::auto_suspend_after`
+    pub last_input: instant::Instant,
+    /// true while the gfx backend is suspended due to `Self::auto_suspend_after`
+    pub auto_suspended: bool,
+    /// taken from `WinitConfig::open_url_callback`
+    pub open_url_callback: Option<Box<dyn FnMut(&egui::output::OpenUrl)>>,
+    /// taken from `WinitConfig::key_map_override`
+    pub key_map_override: Option<Box<dyn Fn(&event::KeyboardInput) -> Option<Key>>>,
+    /// taken from `WinitConfig::clipboard_handler`. used instead of `Self::clipboard` if set
+    pub clipboard_handler: Option<Box<dyn ClipboardHandler>>,
+    /// true while an egui text field has focus (`PlatformOutput::text_cursor_pos` is `Some`). updated after every
+    /// `UserAppData::run`, so inside `run` it still has the value from the previous frame.
+    pub egui_wants_text_input: bool,
+    /// taken from `WinitConfig::soft_keyboard_callback`
+    pub soft_keyboard_callback: Option<Box<dyn FnMut(bool)>>,
+    /// taken from `WinitConfig::show_soft_keyboard`. can be changed at runtime.
+    pub show_soft_keyboard: bool,
+    /// used to show / hide the soft keyboard with `Self::show_soft_keyboard`
+    #[cfg(target_os = "android")]
+    pub android_app: winit::platform::android::activity::AndroidApp,
+    /// system clipboard. `None` if we failed to initialize it. not supported on web / android yet
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+    pub clipboard: Option<arboard::Clipboard>,
 }
 
 impl WindowBackend for WinitBackend {
@@ -71,14 +237,15 @@ impl WindowBackend for WinitBackend {
         #[cfg(target_os = "android")]
         use winit::platform::android::EventLoopBuilderExtAndroid;
         #[cfg(target_os = "android")]
-        let event_loop = event_loop.with_android_app(config.android_app);
+        let event_loop = event_loop.with_android_app(config.android_app.clone());
 
         let el = event_loop.build();
         tracing::error!("this is loggging");
 
         #[allow(unused_mut)]
         let mut window_builder = WindowBuilder::new()
-            .with_resizable(true)
+            .with_resizable(config.resizable)
+            .with_decorations(config.decorations)
             .with_title(&config.title);
         #[cfg(target = "wasm32-unknown-unknown")]
         let window = {
@@ -116,31 +283,94 @@ impl WindowBackend for WinitBackend {
         #[cfg(target_os = "android")]
         let window = None;
 
-        let framebuffer_size = [0, 0];
-        let scale = 1.0;
-
-        let raw_input = RawInput::default();
+        // on android, we won't have a window until resumed. so, we will just use default values
+        let (framebuffer_size, scale) = window
+            .as_ref()
+            .map(|window: &winit::window::Window| {
+                let size = window.inner_size();
+                ([size.width, size.height], window.scale_factor() as f32)
+            })
+            .unwrap_or(([0, 0], 1.0));
+        // set the screen rect (in logical pixels) and scale, so that the first frame is correct even without any resize / scale events
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_two_pos(
+                Default::default(),
+                [
+                    framebuffer_size[0] as f32 / scale,
+                    framebuffer_size[1] as f32 / scale,
+                ]
+                .into(),
+            )),
+            pixels_per_point: Some(scale),
+            ..Default::default()
+        };
         Self {
+            event_loop_proxy: el.create_proxy(),
             event_loop: Some(el),
             window: window,
             modifiers: Modifiers::default(),
+            pressed_keys: HashSet::new(),
             framebuffer_size,
             scale,
+            scale_override: None,
+            virtual_screen_size: None,
             cursor_pos_logical: [0.0, 0.0],
+            raw_mouse_delta: [0.0, 0.0],
+            frame_mouse_delta: [0.0, 0.0],
+            pending_frame_mouse_delta: [0.0, 0.0],
             raw_input,
+            start_time: instant::Instant::now(),
+            last_frame_time: None,
             frame_events: Vec::new(),
             latest_resize_event: true,
             should_close: false,
+            occluded: false,
             backend_config,
             window_builder,
             pointer_touch_id: None,
+            limit_fps_to_monitor: config.limit_fps_to_monitor,
+            control_flow_mode: config.control_flow_mode,
+            last_redraw: None,
+            ime_allowed: false,
+            min_inner_size: None,
+            max_inner_size: None,
+            render_only_if_dirty: config.render_only_if_dirty,
+            frame_dirty_tracker: FrameDirtyTracker::default(),
+            auto_suspend_after: config.auto_suspend_after,
+            coalesce_pointer_moves: config.coalesce_pointer_moves,
+            last_input: instant::Instant::now(),
+            auto_suspended: false,
+            open_url_callback: config.open_url_callback,
+            key_map_override: config.key_map_override,
+            clipboard_handler: config.clipboard_handler,
+            egui_wants_text_input: false,
+            soft_keyboard_callback: config.soft_keyboard_callback,
+            show_soft_keyboard: config.show_soft_keyboard,
+            #[cfg(target_os = "android")]
+            android_app: config.android_app,
+            #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+            clipboard: arboard::Clipboard::new()
+                .map_err(|e| tracing::error!("failed to initialize clipboard: {e}"))
+                .ok(),
         }
     }
 
     fn take_raw_input(&mut self) -> egui::RawInput {
+        // egui needs time for double clicks, animations etc..
+        let time = self.start_time.elapsed().as_secs_f64();
+        if let Some(last_frame_time) = self.last_frame_time {
+            self.raw_input.predicted_dt = ((time - last_frame_time) as f32).min(MAX_PREDICTED_DT);
+        }
+        self.last_frame_time = Some(time);
+        self.raw_input.time = Some(time);
+        self.frame_mouse_delta = std::mem::take(&mut self.pending_frame_mouse_delta);
         self.raw_input.take()
     }
 
+    fn raw_input_mut(&mut self) -> Option<&mut RawInput> {
+        Some(&mut self.raw_input)
+    }
+
     fn get_window(&mut self) -> Option<&mut Self::WindowType> {
         self.window.as_mut()
     }
@@ -158,104 +388,252 @@ impl WindowBackend for WinitBackend {
         mut self,
         mut gfx_backend: G,
         mut user_app: U,
-    ) {
+    ) -> Result<(), AppError> {
         let egui_context = egui::Context::default();
         let mut suspended = true;
-        self.event_loop.take().expect("event loop missing").run(
-            move |event, _event_loop, control_flow| {
-                *control_flow = ControlFlow::Poll;
-
-                match event {
-                    event::Event::Suspended => {
-                        suspended = true;
-                        tracing::warn!("suspend event received");
-                        #[cfg(not(target_os = "android"))]
-                        panic!("suspend on non-android platforms is not supported at the moment");
-                        #[cfg(target_os = "android")]
-                        {
-                            gfx_backend.suspend(&mut self);
-                            self.window = None;
-                        }
+        // when egui wants the next frame. `None` means egui doesn't need a repaint until some input arrives.
+        let mut next_repaint: Option<instant::Instant> = Some(instant::Instant::now());
+        // set by the event handler when the app exits with `AppControlFlow::Error`
+        let app_error: std::rc::Rc<std::cell::RefCell<Option<AppError>>> = Default::default();
+        let event_handler_app_error = app_error.clone();
+        let event_loop = self.event_loop.take().expect("event loop missing");
+        let event_handler = move |event: event::Event<'_, UserEvent>,
+                                  _event_loop: &EventLoopWindowTarget<UserEvent>,
+                                  control_flow: &mut ControlFlow| {
+            match event {
+                event::Event::Suspended => {
+                    suspended = true;
+                    tracing::warn!("suspend event received");
+                    #[cfg(not(target_os = "android"))]
+                    panic!("suspend on non-android platforms is not supported at the moment");
+                    #[cfg(target_os = "android")]
+                    {
+                        gfx_backend.suspend(&mut self);
+                        self.window = None;
                     }
-                    event::Event::Resumed => {
-                        suspended = false;
-                        tracing::warn!("resume event received");
-                        #[cfg(target_os = "android")]
-                        {
-                            self.window = Some(
-                                self.window_builder
-                                    .clone()
-                                    .build(_event_loop)
-                                    .expect("failed to create window"),
-                            );
-                            gfx_backend.resume(&mut self);
-                        }
-                        let framebuffer_size_physical = self
-                            .window
-                            .as_ref()
-                            .expect("failed to get size of window after resume event")
-                            .inner_size();
-
-                        self.framebuffer_size = [
-                            framebuffer_size_physical.width,
-                            framebuffer_size_physical.height,
-                        ];
-                        self.scale = self
-                            .window
+                }
+                event::Event::Resumed => {
+                    suspended = false;
+                    next_repaint = Some(instant::Instant::now());
+                    tracing::warn!("resume event received");
+                    #[cfg(target_os = "android")]
+                    {
+                        self.window = Some(
+                            self.window_builder
+                                .clone()
+                                .build(_event_loop)
+                                .expect("failed to create window"),
+                        );
+                        gfx_backend.resume(&mut self);
+                    }
+                    let framebuffer_size_physical = self
+                        .window
+                        .as_ref()
+                        .expect("failed to get size of window after resume event")
+                        .inner_size();
+
+                    self.framebuffer_size = [
+                        framebuffer_size_physical.width,
+                        framebuffer_size_physical.height,
+                    ];
+                    self.scale = self.scale_override.unwrap_or(
+                        self.window
                             .as_ref()
                             .expect("failed to get scale of window after resume event")
-                            .scale_factor() as f32;
-                        let window_size =
-                            framebuffer_size_physical.to_logical::<f32>(self.scale as f64);
-                        self.raw_input = RawInput {
-                            screen_rect: Some(Rect::from_two_pos(
-                                [0.0, 0.0].into(),
-                                [window_size.width, window_size.height].into(),
-                            )),
-                            pixels_per_point: Some(self.scale),
-                            ..Default::default()
-                        };
+                            .scale_factor() as f32,
+                    );
+                    self.raw_input = RawInput::default();
+                    self.update_screen_rect();
+                    // the window might have been recreated (android) and the scale might have changed
+                    self.apply_size_limits();
+                }
+                event::Event::WindowEvent {
+                    event: event::WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    if user_app.on_close_requested() {
+                        self.should_close = true;
+                    } else {
+                        // the app probably wants to show a confirmation dialog
+                        next_repaint = Some(instant::Instant::now());
                     }
-                    event::Event::MainEventsCleared => {
+                }
+                event::Event::UserEvent(UserEvent::Repaint) => {
+                    next_repaint = Some(instant::Instant::now());
+                }
+                event::Event::MainEventsCleared => {
+                    if !suspended
+                        && !self.auto_suspended
+                        && self
+                            .auto_suspend_deadline()
+                            .map_or(false, |t| t <= instant::Instant::now())
+                    {
+                        tracing::debug!(
+                            "suspending after {:?} without input",
+                            self.auto_suspend_after
+                        );
+                        self.auto_suspended = true;
+                        gfx_backend.suspend(&mut self);
+                    }
+                    // delay the repaint until a refresh interval has passed since the last redraw
+                    if let (Some(repaint), Some(earliest)) = (next_repaint, self.earliest_redraw())
+                    {
+                        next_repaint = Some(repaint.max(earliest));
+                    }
+                    if next_repaint.map_or(false, |t| t <= instant::Instant::now()) {
                         if let Some(window) = self.window.as_ref() {
                             window.request_redraw()
                         }
                     }
-                    event::Event::RedrawRequested(_) => {
-                        if !suspended {
-                            // take egui input
-                            let input = self.take_raw_input();
-                            // prepare surface for drawing
-                            gfx_backend.prepare_frame(self.latest_resize_event, &mut self);
-                            self.latest_resize_event = false;
-                            // begin egui with input
-
-                            // run userapp gui function. let user do anything he wants with window or gfx backends
-                            let output =
-                                user_app.run(&egui_context, input, &mut self, &mut gfx_backend);
+                }
+                event::Event::RedrawRequested(_) => {
+                    if !suspended && !self.occluded && !self.auto_suspended {
+                        self.last_redraw = Some(instant::Instant::now());
+                        // take egui input
+                        let input = self.take_raw_input();
+                        let resized = self.latest_resize_event;
+                        // prepare surface for drawing
+                        gfx_backend.prepare_frame(resized, &mut self);
+                        self.latest_resize_event = false;
+                        // begin egui with input
+
+                        // run userapp gui function. let user do anything he wants with window or gfx backends
+                        let mut output =
+                            user_app.run(&egui_context, input, &mut self, &mut gfx_backend);
+                        // window backends don't use these events (accessibility etc..). they are dropped along with the output.
+                        if !output.platform_output.events.is_empty() {
+                            tracing::trace!(
+                                "ignoring egui output events: {}",
+                                output.platform_output.events_description()
+                            );
+                        }
+                        if let Some(open_url) = output.platform_output.open_url.as_ref() {
+                            self.open_url(open_url);
+                        }
+                        self.update_ime(output.platform_output.text_cursor_pos);
+                        self.update_text_input(output.platform_output.text_cursor_pos.is_some());
+                        if !output.platform_output.copied_text.is_empty() {
+                            // take the text instead of moving it out, as the output is still needed to check if the frame is dirty
+                            self.set_clipboard_text(std::mem::take(
+                                &mut output.platform_output.copied_text,
+                            ));
+                        }
+                        next_repaint = match self.control_flow_mode {
+                            ControlFlowMode::Poll => Some(instant::Instant::now()),
+                            ControlFlowMode::Wait => None,
+                            // egui sets `repaint_after` to Duration::MAX when it doesn't need a repaint. checked_add returns None in that case.
+                            ControlFlowMode::Reactive => {
+                                instant::Instant::now().checked_add(output.repaint_after)
+                            }
+                        };
 
+                        let letterbox = self.letterbox();
+                        let screen_size_logical = letterbox.map_or(
+                            self.physical_to_logical([
+                                self.framebuffer_size[0] as f32,
+                                self.framebuffer_size[1] as f32,
+                            ]),
+                            |letterbox| letterbox.virtual_size,
+                        );
+                        let discarded = self.render_only_if_dirty
+                            && !self.frame_dirty_tracker.is_dirty(
+                                &output,
+                                screen_size_logical,
+                                egui_context.pixels_per_point(),
+                                resized,
+                            )
+                            && gfx_backend.discard_frame(&mut self);
+                        if !discarded {
                             // prepare egui render data for gfx backend
                             let egui_gfx_data = EguiGfxData {
                                 meshes: egui_context.tessellate(output.shapes),
                                 textures_delta: output.textures_delta,
-                                screen_size_logical: [
-                                    self.framebuffer_size[0] as f32 / self.scale,
-                                    self.framebuffer_size[1] as f32 / self.scale,
-                                ],
+                                screen_size_logical,
+                                viewport: letterbox.map(|letterbox| letterbox.viewport),
                             };
                             // render egui with gfx backend
                             gfx_backend.render(egui_gfx_data);
                             // present the frame and loop back
                             gfx_backend.present(&mut self);
                         }
+                        match user_app.control_flow() {
+                            AppControlFlow::Continue => {}
+                            AppControlFlow::Exit => {
+                                self.should_close = true;
+                            }
+                            AppControlFlow::Error(e) => {
+                                tracing::error!("exiting event loop due to app error: {e}");
+                                *event_handler_app_error.borrow_mut() = Some(e);
+                                // `EventLoop::run` never returns. so, the process exits with an error code instead
+                                *control_flow = if RUN_RETURN_SUPPORTED {
+                                    ControlFlow::Exit
+                                } else {
+                                    ControlFlow::ExitWithCode(1)
+                                };
+                                return;
+                            }
+                        }
                     }
-                    rest => self.handle_event(rest),
                 }
-                if self.should_close {
-                    *control_flow = ControlFlow::Exit;
+                rest => {
+                    // any window input (mouse, keyboard, resize etc..) might change the ui, so repaint asap.
+                    if matches!(rest, event::Event::WindowEvent { .. }) {
+                        next_repaint = Some(instant::Instant::now());
+                    }
+                    let events_len = self.raw_input.events.len();
+                    let dropped_files_len = self.raw_input.dropped_files.len();
+                    self.handle_event(rest);
+                    if self.raw_input.events.len() != events_len
+                        || self.raw_input.dropped_files.len() != dropped_files_len
+                    {
+                        self.last_input = instant::Instant::now();
+                        // resume before the next frame, which is the one that lets egui handle this input
+                        if self.auto_suspended {
+                            tracing::debug!("resuming after input");
+                            self.auto_suspended = false;
+                            gfx_backend.resume(&mut self);
+                            // the window might have been resized while suspended
+                            self.latest_resize_event = true;
+                        }
+                    }
                 }
-            },
-        )
+            }
+            if self.should_close {
+                *control_flow = ControlFlow::Exit;
+            } else if self.occluded || self.auto_suspended {
+                // no redraws until the window is visible again (or until the next input, if auto suspended).
+                // un-occlusion and input are window events, which request a repaint.
+                *control_flow = ControlFlow::Wait;
+            } else {
+                // wake up to suspend, even if egui doesn't need a repaint
+                let next_wakeup = match (next_repaint, self.auto_suspend_deadline()) {
+                    (Some(repaint), Some(deadline)) => Some(repaint.min(deadline)),
+                    (repaint, deadline) => repaint.or(deadline),
+                };
+                *control_flow = match next_wakeup {
+                    Some(t) if t <= instant::Instant::now() => ControlFlow::Poll,
+                    Some(t) => ControlFlow::WaitUntil(t),
+                    None => ControlFlow::Wait,
+                };
+            }
+        };
+        // on desktop, the event loop returns. so, the app and gfx backend (owned by the handler) are dropped before we return
+        #[cfg(not(any(target_arch = "wasm32", target_os = "android", target_os = "ios")))]
+        {
+            use winit::platform::run_return::EventLoopExtRunReturn;
+            let mut event_loop = event_loop;
+            event_loop.run_return(event_handler);
+            let app_error = app_error.borrow_mut().take();
+            match app_error {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        }
+        #[cfg(any(target_arch = "wasm32", target_os = "android", target_os = "ios"))]
+        {
+            drop(app_error);
+            event_loop.run(event_handler)
+        }
     }
 
     fn get_config(&self) -> &BackendConfig {
@@ -269,24 +647,267 @@ impl WindowBackend for WinitBackend {
     fn get_proc_address(&mut self, _: &str) -> *const core::ffi::c_void {
         unimplemented!("winit backend doesn't support loading opengl function pointers")
     }
+
+    fn set_cursor_grab(&mut self, mode: CursorGrabMode) {
+        if let Some(window) = self.window.as_ref() {
+            let result = match mode {
+                CursorGrabMode::None => window.set_cursor_grab(winit::window::CursorGrabMode::None),
+                CursorGrabMode::Confined => {
+                    window.set_cursor_grab(winit::window::CursorGrabMode::Confined)
+                }
+                // some platforms (windows) don't support locking. so, we fallback to confining the cursor
+                CursorGrabMode::Locked => window
+                    .set_cursor_grab(winit::window::CursorGrabMode::Locked)
+                    .or_else(|_| window.set_cursor_grab(winit::window::CursorGrabMode::Confined)),
+            };
+            if let Err(e) = result {
+                tracing::warn!("failed to set cursor grab mode {mode:?}: {e}");
+            }
+        }
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_visible(visible);
+        }
+    }
+
+    fn take_raw_mouse_delta(&mut self) -> [f64; 2] {
+        std::mem::take(&mut self.raw_mouse_delta)
+    }
+
+    fn frame_mouse_delta(&self) -> [f64; 2] {
+        self.frame_mouse_delta
+    }
+
+    fn scale_factor(&self) -> [f32; 2] {
+        [self.scale; 2]
+    }
+
+    fn set_scale_factor_override(&mut self, scale: Option<f32>) {
+        self.scale_override = scale;
+        // on android, window might be None when suspended. we will get the scale factor when resumed anyway.
+        let os_scale = self
+            .window
+            .as_ref()
+            .map(|w| w.scale_factor() as f32)
+            .unwrap_or(self.scale);
+        self.scale = scale.unwrap_or(os_scale);
+        self.update_screen_rect();
+        self.latest_resize_event = true;
+    }
+
+    fn set_decorations(&mut self, decorations: bool) {
+        // update the builder too, so that the window recreated after resume (android) keeps the setting
+        self.window_builder = self.window_builder.clone().with_decorations(decorations);
+        if let Some(window) = self.window.as_ref() {
+            window.set_decorations(decorations);
+        }
+    }
+
+    fn set_resizable(&mut self, resizable: bool) {
+        self.window_builder = self.window_builder.clone().with_resizable(resizable);
+        if let Some(window) = self.window.as_ref() {
+            window.set_resizable(resizable);
+        }
+    }
+
+    fn set_virtual_screen_size(&mut self, size: Option<[f32; 2]>) {
+        self.virtual_screen_size = size;
+        self.update_screen_rect();
+        self.latest_resize_event = true;
+    }
+
+    fn set_min_inner_size(&mut self, size: Option<[f32; 2]>) {
+        self.min_inner_size = size;
+        self.apply_size_limits();
+    }
+
+    fn set_max_inner_size(&mut self, size: Option<[f32; 2]>) {
+        self.max_inner_size = size;
+        self.apply_size_limits();
+    }
 }
 
 impl WinitBackend {
-    fn handle_event(&mut self, event: winit::event::Event<()>) {
+    /// sets `Self::min_inner_size` and `Self::max_inner_size` on the window (in physical pixels, to respect the scale override)
+    fn apply_size_limits(&self) {
+        if let Some(window) = self.window.as_ref() {
+            let to_physical = |size: [f32; 2]| {
+                let [width, height] = self.logical_to_physical(size);
+                dpi::PhysicalSize::new(width.round() as u32, height.round() as u32)
+            };
+            window.set_min_inner_size(self.min_inner_size.map(to_physical));
+            window.set_max_inner_size(self.max_inner_size.map(to_physical));
+        }
+    }
+    /// pushes release events for all the keys which are still held down and resets modifiers
+    fn release_pressed_keys(&mut self) {
+        self.modifiers = Modifiers::default();
+        for key in self.pressed_keys.drain() {
+            self.raw_input.events.push(Event::Key {
+                key,
+                pressed: false,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+    /// if `Self::limit_fps_to_monitor` is set, returns the time after which we can redraw without exceeding the refresh rate
+    /// of the window's current monitor. `None` if there's no limit (or the refresh rate is unknown).
+    fn earliest_redraw(&self) -> Option<instant::Instant> {
+        if !self.limit_fps_to_monitor {
+            return None;
+        }
+        let refresh_rate_millihertz = self
+            .window
+            .as_ref()?
+            .current_monitor()?
+            .refresh_rate_millihertz()
+            .filter(|&rate| rate != 0)?;
+        let refresh_interval =
+            std::time::Duration::from_secs_f64(1000.0 / refresh_rate_millihertz as f64);
+        self.last_redraw?.checked_add(refresh_interval)
+    }
+    /// pushes an event into the next frame's input, coalescing pointer moves if `Self::coalesce_pointer_moves` is set
+    fn push_input_event(&mut self, event: Event) {
+        if self.coalesce_pointer_moves {
+            push_coalesced_event(&mut self.raw_input.events, event);
+        } else {
+            self.raw_input.events.push(event);
+        }
+    }
+    /// time after which the gfx backend should be suspended due to `Self::auto_suspend_after`
+    fn auto_suspend_deadline(&self) -> Option<instant::Instant> {
+        self.last_input.checked_add(self.auto_suspend_after?)
+    }
+    /// `Some` if `Self::virtual_screen_size` is set
+    pub fn letterbox(&self) -> Option<Letterbox> {
+        self.virtual_screen_size
+            .map(|virtual_size| Letterbox::new(virtual_size, self.framebuffer_size))
+    }
+    /// sets `raw_input.screen_rect` and `raw_input.pixels_per_point` using the current framebuffer size and scale
+    /// or the virtual screen size
+    fn update_screen_rect(&mut self) {
+        if let Some(letterbox) = self.letterbox() {
+            self.raw_input.screen_rect = Some(Rect::from_two_pos(
+                Default::default(),
+                letterbox.virtual_size.into(),
+            ));
+            self.raw_input.pixels_per_point = Some(letterbox.pixels_per_point);
+        } else {
+            let logical_size = self.physical_to_logical([
+                self.framebuffer_size[0] as f32,
+                self.framebuffer_size[1] as f32,
+            ]);
+            self.raw_input.screen_rect =
+                Some(Rect::from_two_pos(Default::default(), logical_size.into()));
+            self.raw_input.pixels_per_point = Some(self.scale);
+        }
+    }
+    /// converts a position in physical pixels to egui's coordinates (logical or virtual screen space)
+    fn physical_to_egui_pos(&self, physical: [f32; 2]) -> egui::Pos2 {
+        match self.letterbox() {
+            Some(letterbox) => letterbox.physical_to_virtual(physical),
+            None => self.physical_to_logical(physical).into(),
+        }
+    }
+    /// enables the ime while egui has a focused text field (`PlatformOutput::text_cursor_pos` is `Some`) and moves the
+    /// ime's candidate window to egui's text cursor, so that it follows the caret.
+    fn update_ime(&mut self, text_cursor_pos: Option<egui::Pos2>) {
+        let ime_position = text_cursor_pos.map(|pos| match self.letterbox() {
+            Some(letterbox) => letterbox.virtual_to_physical(pos),
+            None => self.logical_to_physical([pos.x, pos.y]),
+        });
+        if let Some(window) = self.window.as_ref() {
+            if self.ime_allowed != ime_position.is_some() {
+                self.ime_allowed = ime_position.is_some();
+                window.set_ime_allowed(self.ime_allowed);
+            }
+            // winit 0.28 only takes a position (the top left corner of the candidate window). egui's text cursor pos is
+            // the bottom left of the caret, so the candidate window appears just below the text.
+            if let Some([x, y]) = ime_position {
+                window.set_ime_position(dpi::PhysicalPosition::new(x, y));
+            }
+        }
+    }
+    /// updates `Self::egui_wants_text_input` and shows / hides the soft keyboard when it changes.
+    fn update_text_input(&mut self, wants_text_input: bool) {
+        if self.egui_wants_text_input == wants_text_input {
+            return;
+        }
+        self.egui_wants_text_input = wants_text_input;
+        if let Some(soft_keyboard_callback) = self.soft_keyboard_callback.as_mut() {
+            soft_keyboard_callback(wants_text_input);
+        }
+        #[cfg(target_os = "android")]
+        if self.show_soft_keyboard {
+            if wants_text_input {
+                self.android_app.show_soft_input(true);
+            } else {
+                self.android_app.hide_soft_input(false);
+            }
+        }
+    }
+    /// returns a handle which can be sent to other threads to wake up the event loop and redraw.
+    pub fn repaint_signal(&self) -> RepaintSignal {
+        RepaintSignal {
+            proxy: self.event_loop_proxy.clone(),
+        }
+    }
+    fn get_clipboard_text(&mut self) -> Option<String> {
+        if let Some(clipboard_handler) = self.clipboard_handler.as_mut() {
+            return clipboard_handler.get();
+        }
+        #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+        if let Some(clipboard) = self.clipboard.as_mut() {
+            return clipboard
+                .get_text()
+                .map_err(|e| tracing::error!("failed to get clipboard text due to error: {e}"))
+                .ok();
+        }
+        None
+    }
+    fn set_clipboard_text(&mut self, text: String) {
+        if let Some(clipboard_handler) = self.clipboard_handler.as_mut() {
+            clipboard_handler.set(text);
+            return;
+        }
+        #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+        if let Some(clipboard) = self.clipboard.as_mut() {
+            if let Err(e) = clipboard.set_text(text) {
+                tracing::error!("failed to set clipboard text due to error: {e}");
+            }
+            return;
+        }
+        tracing::warn!("clipboard is not available. ignoring copied text {text}");
+    }
+    fn open_url(&mut self, open_url: &egui::output::OpenUrl) {
+        if let Some(open_url_callback) = self.open_url_callback.as_mut() {
+            open_url_callback(open_url);
+        } else {
+            tracing::warn!(
+                "ignoring url {} as there's no open url callback",
+                open_url.url
+            );
+        }
+    }
+    fn handle_event(&mut self, event: winit::event::Event<UserEvent>) {
         if let Some(egui_event) = match event {
             event::Event::WindowEvent { event, .. } => match event {
                 event::WindowEvent::Resized(size) => {
-                    let logical_size = size.to_logical::<f32>(self.scale as f64);
-                    self.raw_input.screen_rect = Some(Rect::from_two_pos(
-                        Default::default(),
-                        [logical_size.width, logical_size.height].into(),
-                    ));
-                    self.latest_resize_event = true;
                     self.framebuffer_size = size.into();
+                    self.update_screen_rect();
+                    self.latest_resize_event = true;
                     None
                 }
-                event::WindowEvent::CloseRequested => {
-                    self.should_close = true;
+                // handled in the event loop, as the user app decides whether to close. see `UserAppData::on_close_requested`
+                event::WindowEvent::CloseRequested => None,
+                event::WindowEvent::Focused(focused) => {
+                    self.raw_input.has_focus = focused;
+                    // we won't get release events for keys released while we are not focused (eg: alt + tab)
+                    if !focused {
+                        self.release_pressed_keys();
+                    }
                     None
                 }
                 event::WindowEvent::DroppedFile(df) => {
@@ -305,24 +926,38 @@ impl WinitBackend {
                 }
 
                 event::WindowEvent::ReceivedCharacter(c) => Some(Event::Text(c.to_string())),
+                // only sent while the ime is allowed (see `Self::update_ime`)
+                event::WindowEvent::Ime(ime) => match ime {
+                    event::Ime::Enabled | event::Ime::Disabled => None,
+                    event::Ime::Preedit(text, _) => Some(Event::CompositionUpdate(text)),
+                    event::Ime::Commit(text) => Some(Event::CompositionEnd(text)),
+                },
 
                 event::WindowEvent::KeyboardInput { input, .. } => {
                     let pressed = match input.state {
                         event::ElementState::Pressed => true,
                         event::ElementState::Released => false,
                     };
-                    if let Some(key_code) = input.virtual_keycode {
-                        if let Some(egui_key) = winit_key_to_egui(key_code) {
-                            Some(Event::Key {
-                                key: egui_key,
+                    // `command` is ctrl on windows/linux and cmd on mac (see `winit_modifiers_to_egui`).
+                    // AltGr is reported as Ctrl + Alt on windows. so, we don't treat AltGr + C/X/V as clipboard shortcuts
+                    // and let them fall through to `ReceivedCharacter`, which produces the real character (eg: `@` or `{`)
+                    let command = pressed && self.modifiers.command && !self.modifiers.alt;
+                    match input.virtual_keycode {
+                        Some(VirtualKeyCode::C) if command => Some(Event::Copy),
+                        Some(VirtualKeyCode::X) if command => Some(Event::Cut),
+                        Some(VirtualKeyCode::V) if command => {
+                            self.get_clipboard_text().map(Event::Paste)
+                        }
+                        key_code => self
+                            .key_map_override
+                            .as_ref()
+                            .and_then(|key_map_override| key_map_override(&input))
+                            .or_else(|| key_code.and_then(winit_key_to_egui))
+                            .map(|key| Event::Key {
+                                key,
                                 pressed,
                                 modifiers: self.modifiers,
-                            })
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
+                            }),
                     }
                 }
                 event::WindowEvent::ModifiersChanged(modifiers) => {
@@ -330,18 +965,48 @@ impl WinitBackend {
                     None
                 }
                 event::WindowEvent::CursorMoved { position, .. } => {
-                    let logical = position.to_logical::<f32>(self.scale as f64);
-                    self.cursor_pos_logical = [logical.x, logical.y];
-                    Some(Event::PointerMoved([logical.x, logical.y].into()))
+                    let pos = self.physical_to_egui_pos([position.x as f32, position.y as f32]);
+                    self.cursor_pos_logical = [pos.x, pos.y];
+                    Some(Event::PointerMoved(pos))
                 }
                 event::WindowEvent::CursorLeft { .. } => Some(Event::PointerGone),
-                event::WindowEvent::MouseWheel { delta, .. } => match delta {
-                    event::MouseScrollDelta::LineDelta(x, y) => Some(Event::Scroll([x, y].into())),
-                    event::MouseScrollDelta::PixelDelta(pos) => {
-                        let lpos = pos.to_logical::<f32>(self.scale as f64);
-                        Some(Event::Scroll([lpos.x, lpos.y].into()))
+                event::WindowEvent::MouseWheel { delta, .. } => {
+                    // scroll delta is in points. zoom factor is derived from the scroll delta in lines.
+                    let points_per_line = self.backend_config.scroll_points_per_line;
+                    let (delta, lines_y) = match delta {
+                        event::MouseScrollDelta::LineDelta(x, y) => {
+                            ([x * points_per_line, y * points_per_line], y)
+                        }
+                        event::MouseScrollDelta::PixelDelta(pos) => {
+                            let lpos = self.physical_to_logical([pos.x as f32, pos.y as f32]);
+                            (lpos, lpos[1] / points_per_line)
+                        }
+                    };
+                    // ctrl + scroll is zoom, just like egui's own winit integration
+                    if self.modifiers.ctrl {
+                        Some(Event::Zoom((lines_y * 0.1).exp()))
+                    } else {
+                        Some(Event::Scroll(delta.into()))
                     }
-                },
+                }
+                // trackpad gestures. the variants exist on every platform (so, no cfg is needed), but winit only emits them
+                // on macos. elsewhere, pinch to zoom arrives as ctrl + scroll (or touch events), which egui handles by itself.
+                event::WindowEvent::TouchpadMagnify { delta, .. } => {
+                    Some(Event::Zoom(1.0 + delta as f32))
+                }
+                // egui 0.20 has no rotation event (multi touch rotation is only derived from touch events).
+                // so, two finger rotation on a trackpad is ignored.
+                event::WindowEvent::TouchpadRotate { .. } => {
+                    tracing::trace!("ignoring touchpad rotate event");
+                    None
+                }
+                // smart magnify (double tap with two fingers) toggles between zoomed in and out.
+                // egui has no event for that and the zoom level to toggle to depends on the app.
+                // so, we just log it.
+                event::WindowEvent::SmartMagnify { .. } => {
+                    tracing::trace!("ignoring smart magnify event");
+                    None
+                }
                 event::WindowEvent::MouseInput { state, button, .. } => {
                     let pressed = match state {
                         event::ElementState::Pressed => true,
@@ -354,23 +1019,34 @@ impl WinitBackend {
                         modifiers: self.modifiers,
                     })
                 }
-                event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
-                    self.scale = scale_factor as f32;
-                    self.raw_input.pixels_per_point = Some(scale_factor as f32);
+                event::WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    self.scale = self.scale_override.unwrap_or(scale_factor as f32);
+                    // we accept the size suggested by winit (so, we don't write to `new_inner_size`).
+                    // the window is resized to it after this event, so use it now instead of waiting for the `Resized` event
+                    self.framebuffer_size = [new_inner_size.width, new_inner_size.height];
+                    self.update_screen_rect();
                     self.latest_resize_event = true;
+                    // the size limits are in logical pixels
+                    self.apply_size_limits();
                     None
                 }
 
+                event::WindowEvent::Occluded(occluded) => {
+                    tracing::debug!("window occluded: {occluded}");
+                    self.occluded = occluded;
+                    None
+                }
                 event::WindowEvent::Destroyed => {
                     tracing::warn!("window destroyed");
                     None
                 }
                 event::WindowEvent::Touch(touch) => {
                     // code stolen from eframe(egui-winit).
-                    let pos = egui::pos2(
-                        touch.location.x as f32 / self.scale,
-                        touch.location.y as f32 / self.scale,
-                    );
+                    let pos = self
+                        .physical_to_egui_pos([touch.location.x as f32, touch.location.y as f32]);
                     tracing::warn!("touch event: {} {}", touch.location.x, touch.location.y);
                     self.cursor_pos_logical = [pos.x, pos.y];
                     if self.pointer_touch_id.is_none() || self.pointer_touch_id.unwrap() == touch.id
@@ -390,7 +1066,7 @@ impl WinitBackend {
                                 });
                             }
                             winit::event::TouchPhase::Moved => {
-                                self.raw_input.events.push(Event::PointerMoved(pos));
+                                self.push_input_event(Event::PointerMoved(pos));
                             }
                             winit::event::TouchPhase::Ended => {
                                 self.pointer_touch_id = None;
@@ -432,29 +1108,89 @@ impl WinitBackend {
                 }
                 _ => None,
             },
+            event::Event::DeviceEvent {
+                event: event::DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                for mouse_delta in [
+                    &mut self.raw_mouse_delta,
+                    &mut self.pending_frame_mouse_delta,
+                ] {
+                    mouse_delta[0] += delta.0;
+                    mouse_delta[1] += delta.1;
+                }
+                None
+            }
             _ => None,
         } {
-            self.raw_input.events.push(egui_event);
+            if let Event::Key { key, pressed, .. } = egui_event {
+                if pressed {
+                    self.pressed_keys.insert(key);
+                } else {
+                    self.pressed_keys.remove(&key);
+                }
+            }
+            self.push_input_event(egui_event);
         }
     }
 }
 
+/// `command` is the platform's shortcut modifier: cmd (`logo`) on mac and ctrl everywhere else.
+/// both our clipboard shortcuts and egui's own shortcuts (eg: select all) use it.
 fn winit_modifiers_to_egui(modifiers: ModifiersState) -> Modifiers {
+    let mac_cmd = cfg!(target_os = "macos") && modifiers.logo();
     Modifiers {
         alt: modifiers.alt(),
         ctrl: modifiers.ctrl(),
         shift: modifiers.shift(),
-        // i have no idea what a mac_cmd key is
-        mac_cmd: false,
-        command: modifiers.logo(),
+        mac_cmd,
+        command: if cfg!(target_os = "macos") {
+            mac_cmd
+        } else {
+            modifiers.ctrl()
+        },
     }
 }
+/// `(back, forward)` button numbers of `MouseButton::Other` on the current platform.
+/// winit reports the side buttons of the mouse with the raw platform button numbers.
+#[cfg(target_os = "windows")]
+const BACK_FORWARD_MOUSE_BUTTONS: &[(u16, u16)] = &[(1, 2)]; // XBUTTON1 / XBUTTON2
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const BACK_FORWARD_MOUSE_BUTTONS: &[(u16, u16)] = &[(3, 4)];
+#[cfg(target_arch = "wasm32")]
+const BACK_FORWARD_MOUSE_BUTTONS: &[(u16, u16)] = &[(0, 1)]; // winit subtracts 3 from `MouseEvent.button`
+/// x11 buttons 8 / 9 and wayland (evdev) BTN_SIDE / BTN_EXTRA. they don't overlap, so we check both.
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_arch = "wasm32"
+)))]
+const BACK_FORWARD_MOUSE_BUTTONS: &[(u16, u16)] = &[(8, 9), (0x113, 0x114)];
+
+/// back maps to `Extra1` and forward maps to `Extra2`, just like glfw's buttons 4 / 5.
+/// other unknown buttons are still reported as `Extra1`.
 fn winit_mouse_button_to_egui(mb: winit::event::MouseButton) -> egui::PointerButton {
     match mb {
         MouseButton::Left => egui::PointerButton::Primary,
         MouseButton::Right => egui::PointerButton::Secondary,
         MouseButton::Middle => egui::PointerButton::Middle,
-        MouseButton::Other(_) => egui::PointerButton::Extra1,
+        MouseButton::Other(button) => {
+            if BACK_FORWARD_MOUSE_BUTTONS
+                .iter()
+                .any(|&(_, forward)| forward == button)
+            {
+                egui::PointerButton::Extra2
+            } else {
+                if !BACK_FORWARD_MOUSE_BUTTONS
+                    .iter()
+                    .any(|&(back, _)| back == button)
+                {
+                    tracing::trace!("unknown mouse button {button}. treating it as Extra1");
+                }
+                egui::PointerButton::Extra1
+            }
+        }
     }
 }
 fn winit_key_to_egui(key_code: VirtualKeyCode) -> Option<Key> {
@@ -467,7 +1203,7 @@ fn winit_key_to_egui(key_code: VirtualKeyCode) -> Option<Key> {
         VirtualKeyCode::Escape => Key::Escape,
         VirtualKeyCode::Tab => Key::Tab,
         VirtualKeyCode::Back => Key::Backspace,
-        VirtualKeyCode::Return => Key::Enter,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
         VirtualKeyCode::Space => Key::Space,
 
         VirtualKeyCode::Insert => Key::Insert,
@@ -487,7 +1223,8 @@ fn winit_key_to_egui(key_code: VirtualKeyCode) -> Option<Key> {
         VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Key::Num7,
         VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Key::Num8,
         VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Key::Num9,
-
+        // egui 0.20 doesn't have keys for numpad add / subtract / multiply / divide.
+        // their characters still reach egui as `Event::Text` via `ReceivedCharacter`
         VirtualKeyCode::A => Key::A,
         VirtualKeyCode::B => Key::B,
         VirtualKeyCode::C => Key::C,
@@ -117,12 +117,15 @@ pub fn fake_main() {
         Default::default(),
         BackendConfig {
             gfx_api_type: GfxApiType::NoApi,
+            ..Default::default()
         },
     );
 
     let wgpu_backend = WgpuBackend::new(&mut window_backend, Default::default());
     let app = App::new(&wgpu_backend.device, wgpu_backend.surface_config.format);
-    window_backend.run_event_loop(wgpu_backend, app);
+    window_backend
+        .run_event_loop(wgpu_backend, app)
+        .expect("app exited with an error");
 }
 
 fn main() {
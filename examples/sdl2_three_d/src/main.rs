@@ -67,11 +67,14 @@ pub fn fake_main() {
         config,
         BackendConfig {
             gfx_api_type: egui_backend::GfxApiType::GL,
+            ..Default::default()
         },
     );
     let glow_backend = ThreeDBackend::new(&mut window_backend, Default::default());
     let app = App::new(&glow_backend);
-    window_backend.run_event_loop(glow_backend, app);
+    window_backend
+        .run_event_loop(glow_backend, app)
+        .expect("app exited with an error");
 }
 
 fn main() {
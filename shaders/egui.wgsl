@@ -6,6 +6,7 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
 };
 
+// xy: screen size in logical pixels (points). zw: padding, always zero.
 @group(0) @binding(0) var<uniform> u_screen_size: vec4<f32>;
 
 fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
@@ -47,4 +48,12 @@ fn vs_main(
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return in.color * textureSample(r_tex_color, r_tex_sampler, in.tex_coord);
-}
\ No newline at end of file
+}
+
+// used for user textures whose view has a non-srgb format (eg: Rgba8Unorm), but contains srgb encoded colors.
+// we decode the colors to linear space, just like the gpu does when sampling `*Srgb` formats.
+@fragment
+fn fs_main_srgb_texture(in: VertexOutput) -> @location(0) vec4<f32> {
+    let tex = textureSample(r_tex_color, r_tex_sampler, in.tex_coord);
+    return in.color * vec4<f32>(linear_from_srgb(tex.rgb * vec3<f32>(255.0)), tex.a);
+}
@@ -34,7 +34,9 @@ type GB = WgpuBackend;
 pub fn fake_main<W: WindowBackend>(mut window_backend: W) {
     let gfx_backend = GB::new(&mut window_backend, Default::default());
 
-    window_backend.run_event_loop(gfx_backend, App { check: false });
+    window_backend
+        .run_event_loop(gfx_backend, App { check: false })
+        .expect("app exited with an error");
 }
 
 // // type GB = egui_render_glow::GlowBackend;
@@ -96,6 +98,8 @@ impl<G: GfxBackend<GlfwBackend>> UserAppData<GlfwBackend, G> for App {
         // end frame
         let output = egui_context.end_frame();
         let window = window_backend.get_window().unwrap();
+        // this is the same as `window_backend.egui_wants_pointer_input` after this frame.
+        // any clicks before the next frame will use this passthrough state. so, there's a latency of one frame.
         if egui_context.wants_pointer_input() || egui_context.wants_keyboard_input() {
             window.set_mouse_passthrough(false);
         } else {
@@ -20,6 +20,7 @@ fn main() {
                 );
             })),
             window_callback: None,
+            ..Default::default()
         },
         Default::default(),
     );